@@ -7,6 +7,7 @@ x11rb::atom_manager! {
         _NET_SUPPORTING_WM_CHECK,
         _NET_WM_ALLOWED_ACTIONS,
         _NET_WM_PID,
+        _NET_WM_PING,
 
         _NET_WM_STATE,
         _NET_WM_STATE_MODAL,
@@ -44,6 +45,7 @@ x11rb::atom_manager! {
         _NET_WM_WINDOW_TYPE_NORMAL,
 
         _NET_CLIENT_LIST,
+        _NET_CLIENT_LIST_STACKING,
         _NET_DESKTOP_VIEWPORT,
         _NET_DESKTOP_GEOMETRY,
         _NET_NUMBER_OF_DESKTOPS,
@@ -57,8 +59,12 @@ x11rb::atom_manager! {
 
         _NET_WM_NAME,
 
+        UTF8_STRING,
+
         WM_PROTOCOLS,
         WM_DELETE_WINDOW,
+        WM_TAKE_FOCUS,
+        WM_WINDOW_ROLE,
     }
 }
 
@@ -109,12 +115,15 @@ impl Atoms {
             self._NET_WM_DESKTOP,
             self._NET_WM_STRUT,
             self._NET_CLIENT_LIST,
+            self._NET_CLIENT_LIST_STACKING,
+            self._NET_WORKAREA,
             self._NET_ACTIVE_WINDOW,
             self._NET_DESKTOP_GEOMETRY,
             self._NET_SUPPORTED,
             self._NET_WM_NAME,
             self._NET_WM_ALLOWED_ACTIONS,
             self._NET_WM_PID,
+            self._NET_WM_PING,
         ]
     }
 }
@@ -1,17 +1,20 @@
-use std::marker::PhantomData;
+use std::{marker::PhantomData, process::Command};
 
+use serde::Deserialize;
 use x11rb::{
     connection::Connection,
     protocol::xproto::{
-        AtomEnum, ConnectionExt, CreateWindowAux, EventMask, PropMode, Rectangle, Window,
-        WindowClass,
+        AtomEnum, ChangeGCAux, ConnectionExt, CreateWindowAux, EventMask, ImageFormat, ImageOrder,
+        PropMode, Rectangle, Window, WindowClass,
     },
     wrapper::ConnectionExt as WrapperConnectionExt,
     COPY_DEPTH_FROM_PARENT,
 };
 
-use crate::{wm::WM, WindowState, WindowType, XlibError, RGBA};
+use crate::{wm::WM, BoundingBox, WMCommand, XlibError, RGBA};
 pub const BAR_HEIGHT: u16 = 30;
+/// the environment variable with the clicked button for status commands
+pub const STATUS_BUTTON_ENV: &str = "BUTTON";
 
 pub struct Bar<'a, C: Connection> {
     pub window: Option<Window>,
@@ -19,92 +22,86 @@ pub struct Bar<'a, C: Connection> {
     pub pos: BarPosition,
     pub y: i16,
     pub x: i16,
-    pub status_text: String,
     pub height: u16,
     pub width: u16,
     pub _phantom_data: PhantomData<&'a C>,
 }
 
 impl<'a, C: Connection> Bar<'a, C> {
-    pub fn update_position(&self, wm: &WM<'a, C>) -> Result<(), XlibError> {
-        // let mut monitors = Monitor::get_monitors(drawable)?;
-
-        // for monitor in monitors.iter_mut() {
-        //     if monitor.bar.show {
-        //         monitor.bounding_box.height -= self.height;
-        //         match monitor.bar.pos {
-        //             BarPosition::Top => {
-        //                 monitor.bar.y = monitor.bounding_box.y;
-        //                 monitor.bounding_box.y += self.height;
-        //             }
-        //             BarPosition::Bottom => {
-        //                 monitor.bar.y = monitor.bounding_box.height + monitor.bounding_box.y;
-        //             }
-        //         }
-        //     } else {
-        //         monitor.bar.y = -self.height;
-        //     }
-
-        // if self.show {
-        //     monitor.bounding_box.height -= self.height;
-        //     match monitor.bar.pos {
-        //         BarPosition::Top => {
-        //             monitor.bar.y = monitor.bounding_box.y;
-        //             monitor.bounding_box.y += self.height;
-        //         }
-        //         BarPosition::Bottom => {
-        //             monitor.bar.y = monitor.bounding_box.height + monitor.bounding_box.y;
-        //         }
-        //     }
-        // } else {
-        //     monitor.bar.y = -self.height;
-        // }
+    /// places the bar at the top or bottom of the provided monitor area
+    pub fn update_position(&mut self, bounding_box: &BoundingBox) {
+        self.x = bounding_box.x;
+        self.width = bounding_box.width;
 
-        Ok(())
-    }
-
-    // pub fn update_status(&mut self, xlib: &Xlib, wm: &WM) {
-    //     let root = &wm.connection.setup().roots[wm.screen_num];
-
-    //     wm.connection.get_property(false, root.root);
+        if !self.show {
+            self.y = -(self.height as i16);
+            return;
+        }
 
-    //     if let Ok(status) = get_text_prop(xlib, wm, root.root.into(), xlib::XA_WM_NAME) {
-    //         self.status_text = status;
-    //     }
-    // }
+        self.y = match self.pos {
+            BarPosition::Top => bounding_box.y,
+            BarPosition::Bottom => bounding_box.y + bounding_box.height as i16 - self.height as i16,
+        };
+    }
 }
 
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum BarPosition {
     Top,
     Bottom,
 }
 
 impl<'a, C: Connection> WM<'a, C> {
-    pub fn create_bar(&mut self) -> Result<(), XlibError> {
+    /// creates a bar for every monitor
+    pub fn create_bars(&mut self) -> Result<(), XlibError> {
+        for monitor_index in 0..self.monitors.len() {
+            if self.monitors[monitor_index].bar.show {
+                self.create_bar(monitor_index)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn create_bar(&mut self, monitor_index: usize) -> Result<(), XlibError> {
         let bar_win_id = self.conn_wrapper.connection.generate_id()?;
 
         let root = &self.conn_wrapper.connection.setup().roots[self.screen_num];
+        let (_, screen_height) = self.screen_size;
+
+        let monitor = &mut self.monitors[monitor_index];
+        monitor.bar.update_position(&monitor.bounding_box);
+        let bar = &monitor.bar;
 
         let window_aux = CreateWindowAux::new()
             .event_mask(EventMask::BUTTON_PRESS | EventMask::EXPOSURE)
             .override_redirect(Some(true.into()))
-            .background_pixel(RGBA::new(0xff, 0xff, 0xff, 0).as_argb_u32());
+            .background_pixel(self.config.colors.bar_background.as_argb_u32());
 
         self.conn_wrapper.connection.create_window(
             COPY_DEPTH_FROM_PARENT,
             bar_win_id,
             root.root,
-            0, // self.bounding_box.x.try_into().unwrap(),
-            self.bar.y,
-            self.screen().width_in_pixels, // self.bounding_box.width.try_into().unwrap(),
-            self.bar.height,
+            bar.x,
+            bar.y,
+            bar.width,
+            bar.height,
             0,
             WindowClass::COPY_FROM_PARENT,
             root.root_visual,
             &window_aux,
         )?;
 
-        self.bar.window = Some(bar_win_id);
+        // struts are measured from the edges of the screen, not the monitor
+        let (top, bottom) = match bar.pos {
+            BarPosition::Top => ((bar.y + bar.height as i16) as u32, 0),
+            BarPosition::Bottom => (0, (screen_height as i16 - bar.y) as u32),
+        };
+        let start_x = bar.x as u32;
+        let end_x = (bar.x + bar.width as i16 - 1) as u32;
+
+        monitor.bar.window = Some(bar_win_id);
 
         self.conn_wrapper.connection.change_property8(
             PropMode::REPLACE,
@@ -140,9 +137,9 @@ impl<'a, C: Connection> WM<'a, C> {
                 // right
                 0,
                 // top
-                BAR_HEIGHT as u32,
+                top,
                 // bottom
-                0,
+                bottom,
                 // left_start_y
                 0,
                 // left_end_y
@@ -152,13 +149,13 @@ impl<'a, C: Connection> WM<'a, C> {
                 // right_end_y
                 0,
                 // top_start_x
-                0,
+                if top != 0 { start_x } else { 0 },
                 // top_end_x
-                self.screen().width_in_pixels as u32,
+                if top != 0 { end_x } else { 0 },
                 // bottom_start_x
-                0,
+                if bottom != 0 { start_x } else { 0 },
                 // bottom_end_x
-                0,
+                if bottom != 0 { end_x } else { 0 },
             ],
         )?;
 
@@ -173,55 +170,401 @@ impl<'a, C: Connection> WM<'a, C> {
         Ok(())
     }
 
+    /// reads the status text from the `WM_NAME` of the root window, like `xsetroot -name` sets it
+    pub fn update_status(&mut self) -> Result<(), XlibError> {
+        let status = self.window_title(self.screen().root)?;
+        self.status_text = if status.is_empty() {
+            format!("bunnuafeth-{}", env!("CARGO_PKG_VERSION"))
+        } else {
+            status
+        };
+
+        self.draw_bar()
+    }
+
+    /// where the tags, the layout symbol, the title and the status text are on the bar
+    /// of the monitor, as `(region, x, width)` from left to right
+    fn bar_regions(&self, monitor_index: usize) -> Vec<(BarRegion, i16, u16)> {
+        let monitor = &self.monitors[monitor_index];
+        // the same padding dwm uses around every piece of text
+        let padding = self.fonts.height();
+        let mut regions = Vec::new();
+
+        // long tag names can go past the end of the bar, x is clamped so it doesn't overflow
+        let start = |x: u16| x.min(i16::MAX as u16) as i16;
+        let mut x: u16 = 0;
+        for (index, name) in monitor.tags.names().iter().enumerate() {
+            let width = self.fonts.text_width(name).saturating_add(padding);
+            regions.push((BarRegion::Tag(index), start(x), width));
+            x = x.saturating_add(width);
+        }
+
+        let width = self
+            .fonts
+            .text_width(monitor.layout_manager.layout.symbol())
+            .saturating_add(padding);
+        regions.push((BarRegion::LayoutSymbol, start(x), width));
+        x = x.saturating_add(width);
+
+        // the status is only shown on the selected monitor, like dwm does
+        let status_width = if monitor_index == self.selected_monitor {
+            self.fonts.text_width(&status_display(&self.status_text)) + padding
+        } else {
+            0
+        };
+        let status_width = status_width.min(monitor.bar.width.saturating_sub(x));
+
+        let title_width = monitor
+            .bar
+            .width
+            .saturating_sub(x.saturating_add(status_width));
+        regions.push((BarRegion::Title, start(x), title_width));
+        x = x.saturating_add(title_width);
+
+        if status_width > 0 {
+            regions.push((BarRegion::Status, start(x), status_width));
+        }
+
+        regions
+    }
+
+    /// draws the tags, the layout symbol, the title of the focused window
+    /// and the status text on every bar, like dwm's `drawbar`
     pub fn draw_bar(&self) -> Result<(), XlibError> {
-        if let Some(bar_window) = self.bar.window {
-            // self.conn_wrapper
-            //     .connection
-            //     .poly_fill_rectangle(
-            //         bar_window,
-            //         self.black_gc,
-            //         &[Rectangle {
-            //             x: self.bar.x,
-            //             y: self.bar.y,
-            //             width: self.bar.width,
-            //             height: self.bar.height,
-            //         }],
-            //     )?
-            //     .check()?;
-            if let Some(fw_state) = self.windows.focused() {
-                let _reply = self
-                    .conn_wrapper
-                    .connection
-                    .get_property(
-                        false,
-                        fw_state.window,
-                        AtomEnum::WM_NAME,
-                        AtomEnum::STRING,
-                        0,
-                        std::u32::MAX,
-                    )?
-                    .reply()?;
-                self.conn_wrapper
-                    .connection
-                    .image_text8(
-                        bar_window,
-                        self.black_gc,
-                        1,
-                        10,
-                        fw_state.window.to_string().as_bytes(),
-                    )?
-                    .check()?;
+        let padding = self.fonts.height();
+        let colors = &self.config.colors;
+        let normal = (&colors.bar_foreground, &colors.bar_background);
+        let selected = (
+            &colors.bar_selected_foreground,
+            &colors.bar_selected_background,
+        );
+
+        for (monitor_index, monitor) in self.monitors.iter().enumerate() {
+            let Some(bar_window) = monitor.bar.window else {
+                continue;
+            };
+            let is_selected = monitor_index == self.selected_monitor;
+            // the other monitors show the window that was focused last on them, like dwm does
+            let focused = if is_selected {
+                self.windows
+                    .focused()
+                    .filter(|fw| fw.monitor == monitor_index)
             } else {
-                self.conn_wrapper.connection.image_text8(
-                    bar_window,
-                    self.black_gc,
-                    1,
-                    10,
-                    b"something",
-                )?;
+                self.monitor_selected_window(monitor_index)
+            };
+
+            let occupied = self
+                .windows
+                .windows()
+                .into_iter()
+                .filter(|w| w.monitor == monitor_index)
+                .fold(0, |acc, w| acc | w.tags);
+            let urgent = self.urgent_tags(monitor_index);
+
+            for (region, x, width) in self.bar_regions(monitor_index) {
+                match region {
+                    BarRegion::Tag(index) => {
+                        let Some(mask) = monitor.tags.mask(index) else {
+                            continue;
+                        };
+                        let scheme = if monitor.tags.selected() & mask != 0 {
+                            selected
+                        } else {
+                            normal
+                        };
+                        // urgent tags are drawn inverted
+                        let scheme = if urgent & mask != 0 {
+                            (scheme.1, scheme.0)
+                        } else {
+                            scheme
+                        };
+                        let name = &monitor.tags.names()[index];
+                        self.draw_text(bar_window, x, width, name, scheme)?;
+
+                        // a small square on tags with windows, filled when the focused one is there
+                        if occupied & mask != 0 {
+                            let size = padding / 6 + 2;
+                            let filled = focused.is_some_and(|fw| fw.tags & mask != 0);
+                            self.draw_square(bar_window, x + 1, size, filled, scheme.0)?;
+                        }
+                    }
+                    BarRegion::LayoutSymbol => {
+                        let symbol = monitor.layout_manager.layout.symbol();
+                        self.draw_text(bar_window, x, width, symbol, normal)?;
+                    }
+                    BarRegion::Title => match focused {
+                        Some(fw_state) => {
+                            let scheme = if is_selected { selected } else { normal };
+                            self.draw_text(bar_window, x, width, &fw_state.title, scheme)?;
+                        }
+                        None => self.draw_text(bar_window, x, width, "", normal)?,
+                    },
+                    BarRegion::Status => {
+                        let status = status_display(&self.status_text);
+                        self.draw_text(bar_window, x, width, &status, normal)?;
+                    }
+                }
+            }
+        }
+
+        self.conn_wrapper.connection.flush()?;
+
+        Ok(())
+    }
+
+    /// clicks on the bar select its monitor, then do what dwm does for the clicked region.
+    /// the status text runs the command of the clicked block
+    pub(crate) fn handle_bar_click(
+        &mut self,
+        monitor_index: usize,
+        button: u8,
+        x: i16,
+    ) -> Result<(), XlibError> {
+        let Some((region, region_x, _)) = self
+            .bar_regions(monitor_index)
+            .into_iter()
+            .find(|&(_, region_x, width)| x >= region_x && ((x - region_x) as u16) < width)
+        else {
+            return Ok(());
+        };
+
+        if monitor_index != self.selected_monitor {
+            self.focus_monitor(monitor_index as isize - self.selected_monitor as isize)?;
+        }
+
+        match (region, button) {
+            (BarRegion::Tag(index), 1) => self.run_command(&WMCommand::ViewTag(index)),
+            (BarRegion::Tag(index), 3) => self.run_command(&WMCommand::ToggleViewTag(index)),
+            (BarRegion::LayoutSymbol, 1) => self.run_command(&WMCommand::NextLayout),
+            (BarRegion::LayoutSymbol, 3) => self.run_command(&WMCommand::PreviousLayout),
+            // the title is the one of the window that was focused last on the monitor,
+            // which keeps the focus or gets it back
+            (BarRegion::Title, 1) => {
+                let window = self
+                    .monitor_selected_window(monitor_index)
+                    .map(|w| w.window);
+                match window {
+                    Some(window) => self.focus_window(window),
+                    None => Ok(()),
+                }
             }
+            (BarRegion::Title, 2) => self.run_command(&WMCommand::Zoom),
+            (BarRegion::Status, button) => {
+                let padding = self.fonts.height();
+                let offset = (x - region_x) as u16;
+                self.run_status_command(offset.saturating_sub(padding / 2), button);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// runs the command of the status block at `offset` pixels into the status text,
+    /// with the button in `$BUTTON`, like dwm's statuscmd patch
+    fn run_status_command(&self, offset: u16, button: u8) {
+        let mut end = 0;
+        let block = status_blocks(&self.status_text)
+            .into_iter()
+            .find(|(_, text)| {
+                end += self.fonts.text_width(text);
+                offset < end
+            })
+            .map(|(block, _)| block);
+
+        let Some(command) = block.and_then(|block| self.config.bar.status_commands.get(block))
+        else {
+            return;
+        };
+
+        tracing::debug!("running status command `{command}` for button {button}");
+        if let Err(e) = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env(STATUS_BUTTON_ENV, button.to_string())
+            .spawn()
+        {
+            tracing::error!("status command failed: {e}");
+        }
+    }
+
+    /// draws the text on `width` pixels of the bar from `x`, padded like the rest of the bar
+    /// and cut off with an ellipsis where the space ends
+    fn draw_text(
+        &self,
+        bar_window: Window,
+        x: i16,
+        width: u16,
+        text: &str,
+        scheme: (&RGBA, &RGBA),
+    ) -> Result<(), XlibError> {
+        let height = self.config.bar.height;
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+
+        let setup = self.conn_wrapper.connection.setup();
+        let depth = self.screen().root_depth;
+        let bits_per_pixel = setup
+            .pixmap_formats
+            .iter()
+            .find(|format| format.depth == depth)
+            .map(|format| format.bits_per_pixel);
+        if bits_per_pixel != Some(32) {
+            // every true color visual we care about has 32 bits per pixel,
+            // anything else only gets the background
+            let change = ChangeGCAux::new().foreground(scheme.1.as_argb_u32());
+            self.conn_wrapper
+                .connection
+                .change_gc(self.black_gc, &change)?;
+            self.conn_wrapper.connection.poly_fill_rectangle(
+                bar_window,
+                self.black_gc,
+                &[Rectangle {
+                    x,
+                    y: 0,
+                    width,
+                    height,
+                }],
+            )?;
+            return Ok(());
+        }
+
+        let padding = self.fonts.height();
+        let pixels = self.fonts.render(
+            text,
+            (width, height),
+            padding / 2,
+            width.saturating_sub(padding),
+            scheme,
+        );
+        let data: Vec<u8> = pixels
+            .into_iter()
+            .flat_map(|pixel| match setup.image_byte_order {
+                ImageOrder::MSB_FIRST => pixel.to_be_bytes(),
+                _ => pixel.to_le_bytes(),
+            })
+            .collect();
+
+        // wide bars don't fit in a single request
+        let row_bytes = width as usize * 4;
+        let max_rows = (self.conn_wrapper.connection.maximum_request_bytes() - 24) / row_bytes;
+        for (index, rows) in data.chunks(row_bytes * max_rows.max(1)).enumerate() {
+            self.conn_wrapper.connection.put_image(
+                ImageFormat::Z_PIXMAP,
+                bar_window,
+                self.black_gc,
+                width,
+                (rows.len() / row_bytes) as u16,
+                x,
+                (index * max_rows.max(1)) as i16,
+                0,
+                depth,
+                rows,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// the square on tags with windows, dwm draws the same
+    fn draw_square(
+        &self,
+        bar_window: Window,
+        x: i16,
+        size: u16,
+        filled: bool,
+        color: &RGBA,
+    ) -> Result<(), XlibError> {
+        self.conn_wrapper.connection.change_gc(
+            self.black_gc,
+            &ChangeGCAux::new().foreground(color.as_argb_u32()),
+        )?;
+
+        let square = Rectangle {
+            x,
+            y: 1,
+            width: size,
+            height: size,
+        };
+        if filled {
+            self.conn_wrapper.connection.poly_fill_rectangle(
+                bar_window,
+                self.black_gc,
+                &[square],
+            )?;
+        } else {
+            // rectangle outlines are one pixel bigger than their size
+            let square = Rectangle {
+                width: size - 1,
+                height: size - 1,
+                ..square
+            };
+            self.conn_wrapper
+                .connection
+                .poly_rectangle(bar_window, self.black_gc, &[square])?;
         }
 
         Ok(())
     }
 }
+
+/// the parts of the bar that do something when clicked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BarRegion {
+    Tag(usize),
+    LayoutSymbol,
+    Title,
+    Status,
+}
+
+/// the status text is split into blocks by the bytes below `' '`, and the value of the byte
+/// is the index of the command in `status_commands` that runs when its block is clicked.
+/// the text before the first one is block 0
+fn status_blocks(status: &str) -> Vec<(usize, &str)> {
+    let mut blocks = Vec::new();
+    let mut block = 0;
+    let mut start = 0;
+
+    for (index, c) in status.char_indices().filter(|(_, c)| *c < ' ') {
+        blocks.push((block, &status[start..index]));
+        block = c as usize;
+        start = index + c.len_utf8();
+    }
+    blocks.push((block, &status[start..]));
+
+    blocks
+}
+
+/// the status text without the bytes that split it into blocks
+fn status_display(status: &str) -> String {
+    status.chars().filter(|c| *c >= ' ').collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_without_separators_is_one_block() {
+        assert_eq!(status_blocks("vol 50%"), [(0, "vol 50%")]);
+        assert_eq!(status_blocks(""), [(0, "")]);
+    }
+
+    #[test]
+    fn status_separators_pick_the_block() {
+        assert_eq!(
+            status_blocks("date\u{1}vol 50%\u{3}bat 80%"),
+            [(0, "date"), (1, "vol 50%"), (3, "bat 80%")]
+        );
+        assert_eq!(
+            status_blocks("\u{2}مرحبا\u{2}"),
+            [(0, ""), (2, "مرحبا"), (2, "")]
+        );
+    }
+
+    #[test]
+    fn status_display_hides_separators() {
+        assert_eq!(status_display("date\u{1} vol\u{3} bat"), "date vol bat");
+        assert_eq!(status_display("tab\there"), "tabhere");
+    }
+}
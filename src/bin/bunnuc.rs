@@ -0,0 +1,167 @@
+//! sends commands and queries to a running bunnu over its control socket
+//!
+//! ```bash
+//! bunnuc view_tag 2
+//! bunnuc execute kitty --single-instance
+//! bunnuc query windows
+//! bunnuc subscribe focus title
+//! ```
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    process::ExitCode,
+};
+
+use bunnuafeth::{
+    ipc::{socket_path, EventKind, Query, Request, Response},
+    WMCommand,
+};
+use serde_json::Value;
+
+const USAGE: &str = "usage: bunnuc <command> [argument...]
+       bunnuc query <windows|focused|layout|tags|monitors>
+       bunnuc subscribe [focus|map|unmap|title|layout|fullscreen|tags|urgent|hung...]
+
+commands are the same as the ones in the config file, like `close_window` or `view_tag 2`";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let request = match parse_request(&args) {
+        Ok(request) => request,
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if let Request::Subscribe(_) = request {
+        return match subscribe(&request) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("error: {e}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    match send(&request) {
+        Ok(Response::Ok(Value::Null)) => ExitCode::SUCCESS,
+        Ok(Response::Ok(value)) => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&value).unwrap_or_default()
+            );
+            ExitCode::SUCCESS
+        }
+        Ok(Response::Error(e)) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn parse_request(args: &[String]) -> Result<Request, String> {
+    let Some((name, rest)) = args.split_first() else {
+        return Err(String::from("missing command"));
+    };
+
+    if name == "query" {
+        let [query] = rest else {
+            return Err(String::from("query takes exactly one argument"));
+        };
+        let query: Query = serde_json::from_value(Value::String(query.clone()))
+            .map_err(|_| format!("unknown query `{query}`"))?;
+
+        return Ok(Request::Query(query));
+    }
+
+    if name == "subscribe" {
+        let kinds = rest
+            .iter()
+            .map(|kind| {
+                serde_json::from_value::<EventKind>(Value::String(kind.clone()))
+                    .map_err(|_| format!("unknown event `{kind}`"))
+            })
+            .collect::<Result<_, _>>()?;
+
+        return Ok(Request::Subscribe(kinds));
+    }
+
+    // `view_tag 2` becomes `{"view_tag": 2}`, `move_floating left 20` becomes
+    // `{"move_floating": ["left", 20]}` and `execute kitty -e top` becomes `{"execute": "kitty -e top"}`
+    let command = match rest {
+        [] => serde_json::from_value(Value::String(name.clone())),
+        [arg] => serde_json::from_value(serde_json::json!({ name: parse_arg(arg) })),
+        args => {
+            let list: Vec<Value> = args.iter().map(|arg| parse_arg(arg)).collect();
+            serde_json::from_value(serde_json::json!({ name: list })).or_else(|e| {
+                // commands with a single string argument, like `execute`
+                serde_json::from_value(serde_json::json!({ name: args.join(" ") })).map_err(|_| e)
+            })
+        }
+    };
+    let command: WMCommand = command.map_err(|e| format!("invalid command `{name}`: {e}"))?;
+
+    Ok(Request::Command(command))
+}
+
+/// numbers are sent as numbers, anything else as a string
+fn parse_arg(arg: &str) -> Value {
+    if let Ok(n) = arg.parse::<i64>() {
+        Value::from(n)
+    } else if let Some(n) = arg.parse::<f64>().ok().filter(|n| n.is_finite()) {
+        Value::from(n)
+    } else {
+        Value::String(arg.to_string())
+    }
+}
+
+fn connect(request: &Request) -> std::io::Result<BufReader<UnixStream>> {
+    let path = socket_path();
+    let mut stream = UnixStream::connect(&path).map_err(|e| {
+        std::io::Error::new(
+            e.kind(),
+            format!("can't connect to {}: {e}", path.display()),
+        )
+    })?;
+
+    let mut line = serde_json::to_vec(request)?;
+    line.push(b'\n');
+    stream.write_all(&line)?;
+
+    Ok(BufReader::new(stream))
+}
+
+fn send(request: &Request) -> std::io::Result<Response> {
+    let mut stream = connect(request)?;
+
+    let mut response = String::new();
+    stream.read_line(&mut response)?;
+
+    Ok(serde_json::from_str(&response)?)
+}
+
+/// prints every event as a JSON line until the window manager exits
+fn subscribe(request: &Request) -> std::io::Result<()> {
+    let mut stream = connect(request)?;
+
+    let mut response = String::new();
+    stream.read_line(&mut response)?;
+    if let Response::Error(e) = serde_json::from_str(&response)? {
+        return Err(std::io::Error::other(e));
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    for line in stream.lines() {
+        writeln!(stdout, "{}", line?)?;
+        stdout.flush()?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,1096 @@
+use std::path::PathBuf;
+
+use regex::Regex;
+use serde::Deserialize;
+use toml::Spanned;
+use x11rb::protocol::xproto::{ButtonIndex, ModMask};
+
+use crate::{
+    bar::{BarPosition, BAR_HEIGHT},
+    layout::{
+        Gaps, Layout, TiledLayout, DEFAULT_MAIN_COUNT, DEFAULT_MAIN_FACTOR, MAIN_FACTOR_RANGE,
+    },
+    rules::{Rule, RuleActions, RuleGeometry, WindowKind},
+    tags::{TagMask, DEFAULT_TAGS},
+    wm::BORDER_WIDTH,
+    Direction, Hotkey, MouseHotkey, WMCommand, RGBA,
+};
+
+const CONFIG_DIR: &str = "bunnuafeth";
+const CONFIG_FILE: &str = "config.toml";
+
+// these live in XF86keysym.h, which x11-keysyms doesn't include
+const XF86_MON_BRIGHTNESS_UP: u32 = 0x1008ff02;
+const XF86_MON_BRIGHTNESS_DOWN: u32 = 0x1008ff03;
+const XF86_AUDIO_LOWER_VOLUME: u32 = 0x1008ff11;
+const XF86_AUDIO_MUTE: u32 = 0x1008ff12;
+const XF86_AUDIO_RAISE_VOLUME: u32 = 0x1008ff13;
+const XF86_AUDIO_PLAY: u32 = 0x1008ff14;
+const XF86_AUDIO_STOP: u32 = 0x1008ff15;
+const XF86_AUDIO_PREV: u32 = 0x1008ff16;
+const XF86_AUDIO_NEXT: u32 = 0x1008ff17;
+
+pub struct Config {
+    pub hotkeys: Vec<Hotkey>,
+    pub mouse_hotkeys: Vec<MouseHotkey>,
+    pub tags: Vec<String>,
+    pub border_width: u32,
+    /// windows that ask to be activated (`_NET_ACTIVE_WINDOW` from an application)
+    /// are marked urgent instead of taking the focus
+    pub focus_stealing_prevention: bool,
+    pub colors: Colors,
+    pub bar: BarConfig,
+    pub layout: LayoutConfig,
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Colors {
+    pub focused_border: RGBA,
+    pub normal_border: RGBA,
+    /// border of windows that demand attention
+    pub urgent_border: RGBA,
+    pub background: RGBA,
+    pub bar_background: RGBA,
+    pub bar_foreground: RGBA,
+    /// the selected tags and the title of the focused window in the bar
+    pub bar_selected_background: RGBA,
+    pub bar_selected_foreground: RGBA,
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Self {
+            focused_border: RGBA::CYAN,
+            normal_border: RGBA::BLACK,
+            urgent_border: RGBA::RED,
+            // 6275a6
+            background: RGBA::new(0x62, 0x75, 0xa6, 0),
+            bar_background: RGBA::new(0xff, 0xff, 0xff, 0),
+            bar_foreground: RGBA::BLACK,
+            bar_selected_background: RGBA::CYAN,
+            bar_selected_foreground: RGBA::new(0xee, 0xee, 0xee, 0xff),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BarConfig {
+    pub show: bool,
+    pub position: BarPosition,
+    pub height: u16,
+    /// font family names, or `monospace`, `sans-serif` and `serif` like fontconfig.
+    /// characters missing from a font are drawn with the next one that has them
+    pub fonts: Vec<String>,
+    /// in pixels per em
+    pub font_size: f32,
+    /// the commands clicks on the status text run, picked by the byte before the clicked block,
+    /// with the button in `$BUTTON` like dwm's statuscmd patch
+    pub status_commands: Vec<String>,
+}
+
+impl Default for BarConfig {
+    fn default() -> Self {
+        Self {
+            show: true,
+            position: BarPosition::Top,
+            height: BAR_HEIGHT,
+            fonts: vec!["monospace".to_string()],
+            font_size: 14.0,
+            status_commands: Vec::new(),
+        }
+    }
+}
+
+/// the initial layout settings of every monitor
+#[derive(Debug, Clone)]
+pub struct LayoutConfig {
+    pub default: Layout,
+    pub main_factor: f32,
+    pub main_count: usize,
+    pub gaps: Gaps,
+    pub smart_gaps: bool,
+    pub size_hints: bool,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self {
+            default: Layout::Tiled(TiledLayout::MainStack),
+            main_factor: DEFAULT_MAIN_FACTOR,
+            main_count: DEFAULT_MAIN_COUNT,
+            gaps: Gaps::default(),
+            smart_gaps: true,
+            size_hints: false,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("failed to read config file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Parse(#[from] toml::de::Error),
+
+    #[error("line {line}: {message}")]
+    Invalid { line: usize, message: String },
+}
+
+/// the config file as written by the user, everything is optional
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    border_width: Option<u32>,
+    focus_stealing_prevention: Option<bool>,
+    tags: Option<Spanned<Vec<String>>>,
+    colors: ColorsFile,
+    bar: BarFile,
+    layout: LayoutFile,
+    keys: Option<Vec<KeyFile>>,
+    buttons: Option<Vec<ButtonFile>>,
+    rules: Vec<RuleFile>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ColorsFile {
+    focused_border: Option<Spanned<String>>,
+    normal_border: Option<Spanned<String>>,
+    urgent_border: Option<Spanned<String>>,
+    background: Option<Spanned<String>>,
+    bar_background: Option<Spanned<String>>,
+    bar_foreground: Option<Spanned<String>>,
+    bar_selected_background: Option<Spanned<String>>,
+    bar_selected_foreground: Option<Spanned<String>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct BarFile {
+    show: Option<bool>,
+    position: Option<BarPosition>,
+    height: Option<u16>,
+    fonts: Option<Vec<String>>,
+    font_size: Option<Spanned<f32>>,
+    status_commands: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct LayoutFile {
+    default: Option<Layout>,
+    main_factor: Option<Spanned<f32>>,
+    main_count: Option<usize>,
+    inner_gap: Option<u32>,
+    outer_gap: Option<u32>,
+    smart_gaps: Option<bool>,
+    size_hints: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyFile {
+    #[serde(default)]
+    mods: Vec<Spanned<String>>,
+    key: Spanned<String>,
+    command: WMCommand,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ButtonFile {
+    #[serde(default)]
+    mods: Vec<Spanned<String>>,
+    button: Spanned<u8>,
+    command: WMCommand,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleFile {
+    class: Option<Spanned<String>>,
+    instance: Option<Spanned<String>>,
+    title: Option<Spanned<String>>,
+    role: Option<Spanned<String>>,
+    r#type: Option<Spanned<WindowKind>>,
+    floating: Option<bool>,
+    tag: Option<Spanned<usize>>,
+    monitor: Option<usize>,
+    fullscreen: Option<bool>,
+    geometry: Option<RuleGeometry>,
+    no_focus: Option<bool>,
+    sticky: Option<bool>,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/bunnuafeth/config.toml`, or `~/.config/bunnuafeth/config.toml`
+    pub fn path() -> Option<PathBuf> {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+        Some(config_home.join(CONFIG_DIR).join(CONFIG_FILE))
+    }
+
+    /// loads the config file, and falls back to the built-in defaults
+    /// if it doesn't exist or is invalid
+    pub fn load() -> Config {
+        Config::try_load().unwrap_or_else(|_| {
+            tracing::warn!("using the default config");
+            Config::default()
+        })
+    }
+
+    /// loads the config file, or the built-in defaults if it doesn't exist.
+    /// errors are logged before being returned
+    pub fn try_load() -> Result<Config, ConfigError> {
+        let Some(path) = Config::path() else {
+            tracing::warn!("could not find the config directory, using the default config");
+            return Ok(Config::default());
+        };
+
+        if !path.exists() {
+            tracing::info!(
+                "no config file at {}, using the default config",
+                path.display()
+            );
+            return Ok(Config::default());
+        }
+
+        match Config::from_file(&path) {
+            Ok(config) => {
+                tracing::info!("loaded config from {}", path.display());
+                Ok(config)
+            }
+            Err(e) => {
+                tracing::error!("invalid config file {}: {e}", path.display());
+                Err(e)
+            }
+        }
+    }
+
+    pub fn from_file(path: &std::path::Path) -> Result<Config, ConfigError> {
+        let text = std::fs::read_to_string(path)?;
+
+        Config::from_toml(&text)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Config, ConfigError> {
+        let file: ConfigFile = toml::from_str(text)?;
+        let default = Config::default();
+
+        // a broken binding only loses itself, not every other binding with it
+        let hotkeys = match file.keys {
+            Some(keys) => keys
+                .into_iter()
+                .filter_map(|key| skip_invalid(parse_key(text, key)))
+                .collect(),
+            None => default.hotkeys,
+        };
+
+        let mouse_hotkeys = match file.buttons {
+            Some(buttons) => buttons
+                .into_iter()
+                .filter_map(|button| skip_invalid(parse_button(text, button)))
+                .collect(),
+            None => default.mouse_hotkeys,
+        };
+
+        let color = |value: Option<Spanned<String>>, default: RGBA| match value {
+            Some(value) => parse_color(value.get_ref()).ok_or_else(|| {
+                invalid(
+                    text,
+                    &value,
+                    format!(
+                        "invalid color `{}`, expected `#rrggbb` or `#rrggbbaa`",
+                        value.get_ref()
+                    ),
+                )
+            }),
+            None => Ok(default),
+        };
+
+        let colors = Colors {
+            focused_border: color(file.colors.focused_border, default.colors.focused_border)?,
+            normal_border: color(file.colors.normal_border, default.colors.normal_border)?,
+            urgent_border: color(file.colors.urgent_border, default.colors.urgent_border)?,
+            background: color(file.colors.background, default.colors.background)?,
+            bar_background: color(file.colors.bar_background, default.colors.bar_background)?,
+            bar_foreground: color(file.colors.bar_foreground, default.colors.bar_foreground)?,
+            bar_selected_background: color(
+                file.colors.bar_selected_background,
+                default.colors.bar_selected_background,
+            )?,
+            bar_selected_foreground: color(
+                file.colors.bar_selected_foreground,
+                default.colors.bar_selected_foreground,
+            )?,
+        };
+
+        let font_size = match file.bar.font_size {
+            Some(size) => {
+                // nan isn't finite either
+                if !size.get_ref().is_finite() || *size.get_ref() <= 0.0 {
+                    return Err(invalid(
+                        text,
+                        &size,
+                        "font_size must be a number bigger than 0".to_string(),
+                    ));
+                }
+                size.into_inner()
+            }
+            None => default.bar.font_size,
+        };
+
+        let bar = BarConfig {
+            show: file.bar.show.unwrap_or(default.bar.show),
+            position: file.bar.position.unwrap_or(default.bar.position),
+            height: file.bar.height.unwrap_or(default.bar.height),
+            fonts: file.bar.fonts.unwrap_or(default.bar.fonts),
+            font_size,
+            status_commands: file
+                .bar
+                .status_commands
+                .unwrap_or(default.bar.status_commands),
+        };
+
+        let main_factor = match file.layout.main_factor {
+            Some(factor) => {
+                if !MAIN_FACTOR_RANGE.contains(factor.get_ref()) {
+                    return Err(invalid(
+                        text,
+                        &factor,
+                        format!(
+                            "main_factor must be between {} and {}",
+                            MAIN_FACTOR_RANGE.start(),
+                            MAIN_FACTOR_RANGE.end()
+                        ),
+                    ));
+                }
+                factor.into_inner()
+            }
+            None => default.layout.main_factor,
+        };
+
+        let layout = LayoutConfig {
+            default: file.layout.default.unwrap_or(default.layout.default),
+            main_factor,
+            main_count: file.layout.main_count.unwrap_or(default.layout.main_count),
+            gaps: Gaps {
+                inner: file.layout.inner_gap.unwrap_or(default.layout.gaps.inner),
+                outer: file.layout.outer_gap.unwrap_or(default.layout.gaps.outer),
+            },
+            smart_gaps: file.layout.smart_gaps.unwrap_or(default.layout.smart_gaps),
+            size_hints: file.layout.size_hints.unwrap_or(default.layout.size_hints),
+        };
+
+        let tags = match file.tags {
+            Some(tags) => {
+                // a tag mask is 32 bits wide
+                if tags.get_ref().is_empty() || tags.get_ref().len() > TagMask::BITS as usize {
+                    return Err(invalid(
+                        text,
+                        &tags,
+                        format!("there must be between 1 and {} tags", TagMask::BITS),
+                    ));
+                }
+                tags.into_inner()
+            }
+            None => default.tags,
+        };
+
+        let pattern = |value: Option<Spanned<String>>| {
+            value
+                .map(|value| {
+                    Regex::new(value.get_ref()).map_err(|e| {
+                        invalid(
+                            text,
+                            &value,
+                            format!("invalid pattern `{}`: {e}", value.get_ref()),
+                        )
+                    })
+                })
+                .transpose()
+        };
+
+        let rules = file
+            .rules
+            .into_iter()
+            .map(|rule| {
+                if let Some(tag) = &rule.tag {
+                    if *tag.get_ref() >= tags.len() {
+                        return Err(invalid(
+                            text,
+                            tag,
+                            format!("there is no tag at index {}", tag.get_ref()),
+                        ));
+                    }
+                }
+
+                // docks and desktops aren't managed, so there's nothing to apply the rule to
+                if let Some(kind) = &rule.r#type {
+                    if matches!(kind.get_ref(), WindowKind::Dock | WindowKind::Desktop) {
+                        return Err(invalid(
+                            text,
+                            kind,
+                            "rules can't match dock or desktop windows".to_string(),
+                        ));
+                    }
+                }
+
+                Ok(Rule {
+                    class: pattern(rule.class)?,
+                    instance: pattern(rule.instance)?,
+                    title: pattern(rule.title)?,
+                    role: pattern(rule.role)?,
+                    kind: rule.r#type.map(Spanned::into_inner),
+                    actions: RuleActions {
+                        floating: rule.floating,
+                        tag: rule.tag.map(Spanned::into_inner),
+                        monitor: rule.monitor,
+                        fullscreen: rule.fullscreen,
+                        geometry: rule.geometry,
+                        no_focus: rule.no_focus,
+                        sticky: rule.sticky,
+                    },
+                })
+            })
+            .collect::<Result<_, ConfigError>>()?;
+
+        Ok(Config {
+            hotkeys,
+            mouse_hotkeys,
+            tags,
+            border_width: file.border_width.unwrap_or(default.border_width),
+            focus_stealing_prevention: file
+                .focus_stealing_prevention
+                .unwrap_or(default.focus_stealing_prevention),
+            colors,
+            bar,
+            layout,
+            rules,
+        })
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let mut hotkeys = vec![
+            Hotkey::new(
+                ModMask::M1,
+                x11_keysyms::XK_q,
+                WMCommand::Execute(String::from("kitty")),
+            ),
+            Hotkey::new(
+                ModMask::M1,
+                x11_keysyms::XK_p,
+                WMCommand::Execute(String::from("xprop")),
+            ),
+            Hotkey::new(ModMask::M1, x11_keysyms::XK_c, WMCommand::CloseWindow),
+            Hotkey::new(
+                ModMask::M1 | ModMask::SHIFT,
+                x11_keysyms::XK_c,
+                WMCommand::KillWindow,
+            ),
+            Hotkey::new(ModMask::M1, x11_keysyms::XK_f, WMCommand::ToggleFullscreen),
+            Hotkey::new(ModMask::M1, x11_keysyms::XK_v, WMCommand::ToggleFloating),
+            Hotkey::new(
+                ModMask::M1 | ModMask::SHIFT,
+                x11_keysyms::XK_r,
+                WMCommand::ReloadConfig,
+            ),
+            Hotkey::new(ModMask::M1, x11_keysyms::XK_space, WMCommand::NextLayout),
+            Hotkey::new(
+                ModMask::M1 | ModMask::SHIFT,
+                x11_keysyms::XK_space,
+                WMCommand::PreviousLayout,
+            ),
+            Hotkey::new(
+                ModMask::M1,
+                x11_keysyms::XK_t,
+                WMCommand::SetLayout(Layout::Tiled(TiledLayout::MainStack)),
+            ),
+            Hotkey::new(
+                ModMask::M1,
+                x11_keysyms::XK_m,
+                WMCommand::SetLayout(Layout::Tiled(TiledLayout::Monocle)),
+            ),
+            Hotkey::new(
+                ModMask::M1,
+                x11_keysyms::XK_h,
+                WMCommand::ChangeMainFactor(-0.05),
+            ),
+            Hotkey::new(
+                ModMask::M1,
+                x11_keysyms::XK_l,
+                WMCommand::ChangeMainFactor(0.05),
+            ),
+            Hotkey::new(
+                ModMask::M1,
+                x11_keysyms::XK_i,
+                WMCommand::ChangeMainCount(1),
+            ),
+            Hotkey::new(
+                ModMask::M1,
+                x11_keysyms::XK_d,
+                WMCommand::ChangeMainCount(-1),
+            ),
+            Hotkey::new(
+                ModMask::M1,
+                x11_keysyms::XK_equal,
+                WMCommand::ChangeInnerGap(2),
+            ),
+            Hotkey::new(
+                ModMask::M1,
+                x11_keysyms::XK_minus,
+                WMCommand::ChangeInnerGap(-2),
+            ),
+            Hotkey::new(
+                ModMask::M1 | ModMask::SHIFT,
+                x11_keysyms::XK_equal,
+                WMCommand::ChangeOuterGap(2),
+            ),
+            Hotkey::new(
+                ModMask::M1 | ModMask::SHIFT,
+                x11_keysyms::XK_minus,
+                WMCommand::ChangeOuterGap(-2),
+            ),
+            Hotkey::new(ModMask::M1, x11_keysyms::XK_g, WMCommand::ToggleGaps),
+            Hotkey::new(ModMask::M1, x11_keysyms::XK_j, WMCommand::FocusNext),
+            Hotkey::new(ModMask::M1, x11_keysyms::XK_k, WMCommand::FocusPrevious),
+            Hotkey::new(
+                ModMask::M1 | ModMask::SHIFT,
+                x11_keysyms::XK_j,
+                WMCommand::SwapNext,
+            ),
+            Hotkey::new(
+                ModMask::M1 | ModMask::SHIFT,
+                x11_keysyms::XK_k,
+                WMCommand::SwapPrevious,
+            ),
+            Hotkey::new(
+                ModMask::M1,
+                x11_keysyms::XK_Left,
+                WMCommand::FocusDirection(Direction::Left),
+            ),
+            Hotkey::new(
+                ModMask::M1,
+                x11_keysyms::XK_Right,
+                WMCommand::FocusDirection(Direction::Right),
+            ),
+            Hotkey::new(
+                ModMask::M1,
+                x11_keysyms::XK_Up,
+                WMCommand::FocusDirection(Direction::Up),
+            ),
+            Hotkey::new(
+                ModMask::M1,
+                x11_keysyms::XK_Down,
+                WMCommand::FocusDirection(Direction::Down),
+            ),
+            Hotkey::new(ModMask::M1, x11_keysyms::XK_Return, WMCommand::Zoom),
+            Hotkey::new(ModMask::M1, x11_keysyms::XK_Tab, WMCommand::FocusLast),
+            Hotkey::new(ModMask::M1, x11_keysyms::XK_r, WMCommand::ResizeMode),
+            Hotkey::new(
+                ModMask::M1,
+                x11_keysyms::XK_period,
+                WMCommand::FocusNextMonitor,
+            ),
+            Hotkey::new(
+                ModMask::M1,
+                x11_keysyms::XK_comma,
+                WMCommand::FocusPreviousMonitor,
+            ),
+            Hotkey::new(
+                ModMask::M1 | ModMask::SHIFT,
+                x11_keysyms::XK_period,
+                WMCommand::MoveToNextMonitor,
+            ),
+            Hotkey::new(
+                ModMask::M1 | ModMask::SHIFT,
+                x11_keysyms::XK_comma,
+                WMCommand::MoveToPreviousMonitor,
+            ),
+        ];
+
+        let tag_keys = [
+            x11_keysyms::XK_1,
+            x11_keysyms::XK_2,
+            x11_keysyms::XK_3,
+            x11_keysyms::XK_4,
+            x11_keysyms::XK_5,
+            x11_keysyms::XK_6,
+            x11_keysyms::XK_7,
+            x11_keysyms::XK_8,
+            x11_keysyms::XK_9,
+        ];
+
+        for (index, keysym) in tag_keys.into_iter().enumerate() {
+            hotkeys.extend([
+                Hotkey::new(ModMask::M1, keysym, WMCommand::ViewTag(index)),
+                Hotkey::new(
+                    ModMask::M1 | ModMask::CONTROL,
+                    keysym,
+                    WMCommand::ToggleViewTag(index),
+                ),
+                Hotkey::new(
+                    ModMask::M1 | ModMask::SHIFT,
+                    keysym,
+                    WMCommand::MoveToTag(index),
+                ),
+                Hotkey::new(
+                    ModMask::M1 | ModMask::CONTROL | ModMask::SHIFT,
+                    keysym,
+                    WMCommand::ToggleTag(index),
+                ),
+            ]);
+        }
+
+        let directions = [
+            (x11_keysyms::XK_Left, Direction::Left),
+            (x11_keysyms::XK_Right, Direction::Right),
+            (x11_keysyms::XK_Up, Direction::Up),
+            (x11_keysyms::XK_Down, Direction::Down),
+        ];
+
+        for (keysym, direction) in directions {
+            hotkeys.extend([
+                Hotkey::new(
+                    ModMask::M1 | ModMask::CONTROL,
+                    keysym,
+                    WMCommand::MoveFloating(direction, 20),
+                ),
+                Hotkey::new(
+                    ModMask::M1 | ModMask::CONTROL | ModMask::SHIFT,
+                    keysym,
+                    WMCommand::ResizeFloating(direction, 20),
+                ),
+            ]);
+        }
+
+        let mouse_hotkeys = vec![
+            MouseHotkey::new(ModMask::M1, ButtonIndex::M1, WMCommand::MoveWindow),
+            MouseHotkey::new(ModMask::M1, ButtonIndex::M3, WMCommand::ResizeWindow(0)),
+        ];
+
+        Self {
+            hotkeys,
+            mouse_hotkeys,
+            tags: DEFAULT_TAGS.iter().map(|t| t.to_string()).collect(),
+            border_width: BORDER_WIDTH,
+            focus_stealing_prevention: true,
+            colors: Colors::default(),
+            bar: BarConfig::default(),
+            layout: LayoutConfig::default(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+fn invalid<T>(text: &str, spanned: &Spanned<T>, message: String) -> ConfigError {
+    ConfigError::Invalid {
+        line: line_of(text, spanned.span().start),
+        message,
+    }
+}
+
+/// 1-based line number of a byte offset
+fn line_of(text: &str, offset: usize) -> usize {
+    text[..offset.min(text.len())].matches('\n').count() + 1
+}
+
+/// logs a binding that couldn't be parsed, so it can be left out
+fn skip_invalid<T>(binding: Result<T, ConfigError>) -> Option<T> {
+    binding
+        .map_err(|e| tracing::warn!("skipping invalid binding, {e}"))
+        .ok()
+}
+
+fn parse_key(text: &str, key: KeyFile) -> Result<Hotkey, ConfigError> {
+    let modmask = parse_mods(text, &key.mods)?;
+    let keysym = keysym_from_name(key.key.get_ref()).ok_or_else(|| {
+        invalid(
+            text,
+            &key.key,
+            format!("unknown key name `{}`", key.key.get_ref()),
+        )
+    })?;
+
+    Ok(Hotkey::new(modmask, keysym, key.command))
+}
+
+fn parse_button(text: &str, button: ButtonFile) -> Result<MouseHotkey, ConfigError> {
+    let mods = parse_mods(text, &button.mods)?;
+    let index = *button.button.get_ref();
+    if !(1..=5).contains(&index) {
+        return Err(invalid(
+            text,
+            &button.button,
+            format!("button must be between 1 and 5, got {index}"),
+        ));
+    }
+
+    Ok(MouseHotkey::new(
+        mods,
+        ButtonIndex::from(index),
+        button.command,
+    ))
+}
+
+fn parse_mods(text: &str, mods: &[Spanned<String>]) -> Result<ModMask, ConfigError> {
+    mods.iter().try_fold(ModMask::from(0u16), |acc, name| {
+        modmask_from_name(name.get_ref())
+            .map(|modmask| acc | modmask)
+            .ok_or_else(|| {
+                invalid(
+                    text,
+                    name,
+                    format!(
+                        "unknown modifier `{}`, expected one of shift, lock, control, \
+                         alt, super, mod1, mod2, mod3, mod4 or mod5",
+                        name.get_ref()
+                    ),
+                )
+            })
+    })
+}
+
+fn modmask_from_name(name: &str) -> Option<ModMask> {
+    let modmask = match name.to_lowercase().as_str() {
+        "shift" => ModMask::SHIFT,
+        "lock" | "caps_lock" => ModMask::LOCK,
+        "control" | "ctrl" => ModMask::CONTROL,
+        "mod1" | "alt" => ModMask::M1,
+        "mod2" => ModMask::M2,
+        "mod3" => ModMask::M3,
+        "mod4" | "super" | "win" => ModMask::M4,
+        "mod5" => ModMask::M5,
+        _ => return None,
+    };
+
+    Some(modmask)
+}
+
+/// converts an X keysym name (like the ones `xev` prints) to its keysym
+fn keysym_from_name(name: &str) -> Option<u32> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        // latin-1 keysyms have the same value as their code point
+        let code = c as u32;
+        if (0x20..=0x7e).contains(&code) || (0xa0..=0xff).contains(&code) {
+            return Some(code);
+        }
+    }
+
+    if let Some(n) = name
+        .strip_prefix('F')
+        .and_then(|n| n.parse::<u32>().ok())
+        .filter(|n| (1..=35).contains(n))
+    {
+        return Some(x11_keysyms::XK_F1 + n - 1);
+    }
+
+    let keysym = match name {
+        "space" => x11_keysyms::XK_space,
+        "Return" => x11_keysyms::XK_Return,
+        "Tab" => x11_keysyms::XK_Tab,
+        "Escape" => x11_keysyms::XK_Escape,
+        "BackSpace" => x11_keysyms::XK_BackSpace,
+        "Delete" => x11_keysyms::XK_Delete,
+        "Insert" => x11_keysyms::XK_Insert,
+        "Home" => x11_keysyms::XK_Home,
+        "End" => x11_keysyms::XK_End,
+        "Prior" | "Page_Up" => x11_keysyms::XK_Prior,
+        "Next" | "Page_Down" => x11_keysyms::XK_Next,
+        "Left" => x11_keysyms::XK_Left,
+        "Right" => x11_keysyms::XK_Right,
+        "Up" => x11_keysyms::XK_Up,
+        "Down" => x11_keysyms::XK_Down,
+        "Print" => x11_keysyms::XK_Print,
+        "Menu" => x11_keysyms::XK_Menu,
+        "comma" => x11_keysyms::XK_comma,
+        "period" => x11_keysyms::XK_period,
+        "slash" => x11_keysyms::XK_slash,
+        "backslash" => x11_keysyms::XK_backslash,
+        "semicolon" => x11_keysyms::XK_semicolon,
+        "apostrophe" => x11_keysyms::XK_apostrophe,
+        "grave" => x11_keysyms::XK_grave,
+        "minus" => x11_keysyms::XK_minus,
+        "equal" => x11_keysyms::XK_equal,
+        "plus" => x11_keysyms::XK_plus,
+        "bracketleft" => x11_keysyms::XK_bracketleft,
+        "bracketright" => x11_keysyms::XK_bracketright,
+        "XF86MonBrightnessUp" => XF86_MON_BRIGHTNESS_UP,
+        "XF86MonBrightnessDown" => XF86_MON_BRIGHTNESS_DOWN,
+        "XF86AudioLowerVolume" => XF86_AUDIO_LOWER_VOLUME,
+        "XF86AudioMute" => XF86_AUDIO_MUTE,
+        "XF86AudioRaiseVolume" => XF86_AUDIO_RAISE_VOLUME,
+        "XF86AudioPlay" => XF86_AUDIO_PLAY,
+        "XF86AudioStop" => XF86_AUDIO_STOP,
+        "XF86AudioPrev" => XF86_AUDIO_PREV,
+        "XF86AudioNext" => XF86_AUDIO_NEXT,
+        _ => return None,
+    };
+
+    Some(keysym)
+}
+
+/// parses `#rrggbb` or `#rrggbbaa`
+fn parse_color(color: &str) -> Option<RGBA> {
+    let hex = color.strip_prefix('#')?;
+    if !hex.is_ascii() || !(hex.len() == 6 || hex.len() == 8) {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    let alpha = if hex.len() == 8 { channel(6)? } else { 0xff };
+
+    Some(RGBA::new(channel(0)?, channel(2)?, channel(4)?, alpha))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the line of the error, panics if the config is valid
+    fn error_line(text: &str) -> usize {
+        match Config::from_toml(text) {
+            Err(ConfigError::Invalid { line, .. }) => line,
+            Err(e) => panic!("expected an invalid value error, got {e}"),
+            Ok(_) => panic!("expected the config to be invalid"),
+        }
+    }
+
+    #[test]
+    fn empty_config_uses_defaults() {
+        let config = Config::from_toml("").unwrap();
+        let default = Config::default();
+
+        assert_eq!(config.tags, default.tags);
+        assert_eq!(config.hotkeys.len(), default.hotkeys.len());
+        assert_eq!(config.bar.font_size, default.bar.font_size);
+        assert_eq!(config.layout.default, default.layout.default);
+    }
+
+    #[test]
+    fn values_override_defaults() {
+        let config = Config::from_toml(
+            r##"
+border_width = 3
+tags = ["web", "code"]
+
+[colors]
+focused_border = "#ff0000"
+
+[bar]
+position = "bottom"
+font_size = 11.5
+
+[layout]
+default = "grid"
+main_factor = 0.6
+inner_gap = 4
+"##,
+        )
+        .unwrap();
+
+        assert_eq!(config.border_width, 3);
+        assert_eq!(config.tags, ["web", "code"]);
+        assert_eq!(config.colors.focused_border, RGBA::new(0xff, 0, 0, 0xff));
+        assert!(matches!(config.bar.position, BarPosition::Bottom));
+        assert_eq!(config.bar.font_size, 11.5);
+        assert_eq!(config.layout.default, Layout::Tiled(TiledLayout::Grid));
+        assert_eq!(config.layout.main_factor, 0.6);
+        assert_eq!(config.layout.gaps.inner, 4);
+    }
+
+    #[test]
+    fn invalid_color_reports_line() {
+        assert_eq!(error_line("\n[colors]\nbackground = \"blue\"\n"), 3);
+    }
+
+    #[test]
+    fn invalid_keys_are_skipped() {
+        let keys = r#"
+[[keys]]
+mods = ["alt"]
+key = "NotAKey"
+command = "zoom"
+
+[[keys]]
+mods = ["alt", "hyper"]
+key = "q"
+command = "zoom"
+
+[[keys]]
+mods = ["alt"]
+key = "Return"
+command = "zoom"
+"#;
+        let config = Config::from_toml(keys).unwrap();
+        assert_eq!(config.hotkeys.len(), 1);
+    }
+
+    #[test]
+    fn invalid_buttons_are_skipped() {
+        let buttons = r#"
+[[buttons]]
+button = 9
+command = "move_window"
+
+[[buttons]]
+mods = ["hyper"]
+button = 1
+command = "move_window"
+
+[[buttons]]
+mods = ["super"]
+button = 3
+command = "move_window"
+"#;
+        let config = Config::from_toml(buttons).unwrap();
+        assert_eq!(config.mouse_hotkeys.len(), 1);
+    }
+
+    #[test]
+    fn invalid_bindings_report_line() {
+        let unknown_key = r#"
+[[keys]]
+mods = ["alt"]
+key = "NotAKey"
+command = "zoom"
+"#;
+        let file: ConfigFile = toml::from_str(unknown_key).unwrap();
+        let key = file.keys.unwrap().pop().unwrap();
+        assert!(matches!(
+            parse_key(unknown_key, key),
+            Err(ConfigError::Invalid { line: 4, .. })
+        ));
+
+        let unknown_modifier =
+            "[[keys]]\nmods = [\"alt\",\n    \"hyper\"]\nkey = \"q\"\ncommand = \"zoom\"\n";
+        let file: ConfigFile = toml::from_str(unknown_modifier).unwrap();
+        let key = file.keys.unwrap().pop().unwrap();
+        assert!(matches!(
+            parse_key(unknown_modifier, key),
+            Err(ConfigError::Invalid { line: 3, .. })
+        ));
+
+        let invalid_button = "[[buttons]]\nbutton = 9\ncommand = \"move_window\"\n";
+        let file: ConfigFile = toml::from_str(invalid_button).unwrap();
+        let button = file.buttons.unwrap().pop().unwrap();
+        assert!(matches!(
+            parse_button(invalid_button, button),
+            Err(ConfigError::Invalid { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn font_size_must_be_positive_number() {
+        assert_eq!(error_line("[bar]\nfont_size = 0.0\n"), 2);
+        assert_eq!(error_line("[bar]\nfont_size = -3.0\n"), 2);
+        assert_eq!(error_line("[bar]\nfont_size = nan\n"), 2);
+        assert_eq!(error_line("[bar]\nfont_size = inf\n"), 2);
+    }
+
+    #[test]
+    fn main_factor_must_be_in_range() {
+        assert_eq!(error_line("[layout]\nmain_factor = 1.0\n"), 2);
+        assert_eq!(error_line("[layout]\nmain_factor = nan\n"), 2);
+    }
+
+    #[test]
+    fn tags_must_fit_in_mask() {
+        assert_eq!(error_line("border_width = 1\ntags = []\n"), 2);
+
+        let tags: Vec<String> = (1..=33).map(|n| format!("\"{n}\"")).collect();
+        assert_eq!(error_line(&format!("tags = [{}]\n", tags.join(", "))), 1);
+
+        let tags: Vec<String> = (1..=32).map(|n| format!("\"{n}\"")).collect();
+        let config = Config::from_toml(&format!("tags = [{}]\n", tags.join(", "))).unwrap();
+        assert_eq!(config.tags.len(), 32);
+    }
+
+    #[test]
+    fn rule_tag_must_exist() {
+        let text = r#"
+tags = ["a", "b"]
+
+[[rules]]
+class = "firefox"
+tag = 2
+"#;
+        assert_eq!(error_line(text), 6);
+    }
+
+    #[test]
+    fn rules_cant_match_unmanaged_kinds() {
+        assert_eq!(
+            error_line("[[rules]]\nfloating = true\ntype = \"dock\"\n"),
+            3
+        );
+        assert_eq!(error_line("[[rules]]\ntype = \"desktop\"\n"), 2);
+
+        let config = Config::from_toml("[[rules]]\ntype = \"dialog\"\n").unwrap();
+        assert_eq!(config.rules[0].kind, Some(WindowKind::Dialog));
+    }
+
+    #[test]
+    fn invalid_rule_pattern_reports_line() {
+        assert_eq!(error_line("[[rules]]\ntitle = \"(unclosed\"\n"), 2);
+    }
+
+    #[test]
+    fn unknown_field_is_parse_error() {
+        assert!(matches!(
+            Config::from_toml("[bar]\ncolour = 1\n"),
+            Err(ConfigError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn line_of_offsets() {
+        let text = "a\nbc\n\nd";
+        assert_eq!(line_of(text, 0), 1);
+        assert_eq!(line_of(text, 2), 2);
+        assert_eq!(line_of(text, 5), 3);
+        assert_eq!(line_of(text, 6), 4);
+        assert_eq!(line_of(text, 100), 4);
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(
+            parse_color("#102030"),
+            Some(RGBA::new(0x10, 0x20, 0x30, 0xff))
+        );
+        assert_eq!(
+            parse_color("#10203040"),
+            Some(RGBA::new(0x10, 0x20, 0x30, 0x40))
+        );
+        assert_eq!(parse_color("102030"), None);
+        assert_eq!(parse_color("#1020"), None);
+        assert_eq!(parse_color("#10203g"), None);
+        assert_eq!(parse_color("#ééé"), None);
+    }
+
+    #[test]
+    fn key_names() {
+        assert_eq!(keysym_from_name("a"), Some('a' as u32));
+        assert_eq!(keysym_from_name("F12"), Some(x11_keysyms::XK_F1 + 11));
+        assert_eq!(keysym_from_name("F36"), None);
+        assert_eq!(keysym_from_name("Return"), Some(x11_keysyms::XK_Return));
+        assert_eq!(keysym_from_name("XF86AudioMute"), Some(XF86_AUDIO_MUTE));
+        assert_eq!(keysym_from_name("nope"), None);
+    }
+
+    #[test]
+    fn modifier_names_ignore_case() {
+        assert_eq!(modmask_from_name("Super"), Some(ModMask::M4));
+        assert_eq!(modmask_from_name("CTRL"), Some(ModMask::CONTROL));
+        assert_eq!(modmask_from_name("meta"), None);
+    }
+}
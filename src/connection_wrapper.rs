@@ -53,7 +53,7 @@ impl<'a, C: Connection> ConnWrapper<'a, C> {
         if window_props.below {
             props.push(self.atoms._NET_WM_STATE_BELOW);
         }
-        if window_props.demands_attention {
+        if window_props.is_urgent() {
             props.push(self.atoms._NET_WM_STATE_DEMANDS_ATTENTION);
         }
 
@@ -0,0 +1,370 @@
+//! anti-aliased text for the bar.
+//!
+//! fonts are looked up with fontdb, text is split into right-to-left and left-to-right runs
+//! with unicode-bidi, shaped with rustybuzz so arabic letters join, and drawn with ab_glyph.
+
+use ab_glyph::{Font, FontRef, GlyphId, PxScale, ScaleFont};
+use fontdb::{Database, Family, Query, Style, Weight};
+use rustybuzz::{Direction, UnicodeBuffer};
+use unicode_bidi::BidiInfo;
+
+use crate::RGBA;
+
+/// used when none of the configured fonts can be found
+const DEFAULT_FONTS: [Family<'static>; 2] = [Family::Monospace, Family::SansSerif];
+
+/// a font face loaded from disk, parsed once for drawing and once for shaping
+struct Face {
+    /// borrows `_data`
+    font: FontRef<'static>,
+    /// borrows `_data` too, kept so the font isn't parsed again every time text is shaped
+    shaper: rustybuzz::Face<'static>,
+    /// pixels per font unit
+    scale_factor: f32,
+    /// the font file, the last field so it's dropped after the faces that borrow it
+    _data: Vec<u8>,
+}
+
+impl Face {
+    fn has_glyph(&self, c: char) -> bool {
+        self.font.glyph_id(c).0 != 0
+    }
+
+    fn px_scale(&self) -> PxScale {
+        PxScale::from(self.font.height_unscaled() * self.scale_factor)
+    }
+}
+
+/// a glyph placed on a line of text, `x` is relative to the start of the line
+struct PlacedGlyph {
+    face: usize,
+    id: GlyphId,
+    x: f32,
+    y: f32,
+}
+
+/// shaped text, in the order it's drawn from left to right
+struct Line {
+    glyphs: Vec<PlacedGlyph>,
+    width: f32,
+}
+
+/// the configured fonts, the first one that has a glyph for a character draws it
+pub struct Fonts {
+    faces: Vec<Face>,
+    ascent: f32,
+    height: f32,
+}
+
+impl Fonts {
+    /// loads the fonts with the provided family names, `size` is in pixels per em.
+    /// fonts that can't be found are skipped, and the default font is used when none is left
+    pub fn load(names: &[String], size: f32) -> Fonts {
+        let mut db = Database::new();
+        db.load_system_fonts();
+
+        let mut faces: Vec<Face> = names
+            .iter()
+            .filter_map(|name| {
+                let face = load_face(&db, family(name), size);
+                if face.is_none() {
+                    tracing::warn!("font `{name}` not found");
+                }
+                face
+            })
+            .collect();
+
+        if faces.is_empty() {
+            faces.extend(
+                DEFAULT_FONTS
+                    .into_iter()
+                    .find_map(|family| load_face(&db, family, size))
+                    // any font is better than no text at all
+                    .or_else(|| db.faces().find_map(|info| face_from_db(&db, info.id, size))),
+            );
+        }
+
+        let (ascent, height) = match faces.first() {
+            Some(face) => {
+                let scaled = face.font.as_scaled(face.px_scale());
+                (scaled.ascent(), scaled.height())
+            }
+            None => {
+                tracing::error!("no fonts found, the bar won't have any text");
+                (size, size)
+            }
+        };
+
+        Fonts {
+            faces,
+            ascent,
+            height,
+        }
+    }
+
+    /// the height of a line of text
+    pub fn height(&self) -> u16 {
+        self.height.ceil() as u16
+    }
+
+    /// the width the text takes when drawn
+    pub fn text_width(&self, text: &str) -> u16 {
+        self.shape(text).width.ceil() as u16
+    }
+
+    /// draws the text over the background, starting `x` pixels in and vertically centered,
+    /// text that doesn't fit in `max_width` is cut off with an ellipsis.
+    /// returns the pixels row by row as `0xRRGGBB`
+    pub fn render(
+        &self,
+        text: &str,
+        (width, height): (u16, u16),
+        x: u16,
+        max_width: u16,
+        (foreground, background): (&RGBA, &RGBA),
+    ) -> Vec<u32> {
+        let mut pixels = vec![rgb(background); width as usize * height as usize];
+        if text.is_empty() || self.faces.is_empty() {
+            return pixels;
+        }
+
+        let line = self.fit(text, max_width as f32);
+        let baseline = ((height as f32 - self.height) / 2.0).floor() + self.ascent;
+
+        for glyph in line.glyphs {
+            let face = &self.faces[glyph.face];
+            let glyph = glyph.id.with_scale_and_position(
+                face.px_scale(),
+                ab_glyph::point(x as f32 + glyph.x, baseline - glyph.y),
+            );
+            let Some(outline) = face.font.outline_glyph(glyph) else {
+                continue;
+            };
+
+            let bounds = outline.px_bounds();
+            outline.draw(|gx, gy, coverage| {
+                let px = bounds.min.x as i32 + gx as i32;
+                let py = bounds.min.y as i32 + gy as i32;
+                if px < 0 || py < 0 || px >= width as i32 || py >= height as i32 {
+                    return;
+                }
+
+                let pixel = &mut pixels[py as usize * width as usize + px as usize];
+                *pixel = blend(*pixel, rgb(foreground), coverage);
+            });
+        }
+
+        pixels
+    }
+
+    /// the text shaped as is if it fits, otherwise as much of it as fits followed by an ellipsis
+    fn fit(&self, text: &str, max_width: f32) -> Line {
+        let line = self.shape(text);
+        if line.width <= max_width {
+            return line;
+        }
+
+        let ellipsis = if self.faces.iter().any(|face| face.has_glyph('…')) {
+            "…"
+        } else {
+            "..."
+        };
+
+        // the longest prefix that fits, shaping is not linear so every candidate is shaped
+        let boundaries: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+        let (mut low, mut high) = (0, boundaries.len());
+        let mut best = self.shape(ellipsis);
+        while low < high {
+            let middle = (low + high) / 2;
+            let candidate = self.shape(&format!(
+                "{}{ellipsis}",
+                &text[..boundaries[middle]].trim_end()
+            ));
+            if candidate.width <= max_width {
+                best = candidate;
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+
+        best
+    }
+
+    /// lays the text out from left to right, reordering right-to-left runs
+    /// and shaping every run with the fonts that have its characters
+    fn shape(&self, text: &str) -> Line {
+        let mut line = Line {
+            glyphs: Vec::new(),
+            width: 0.0,
+        };
+        if self.faces.is_empty() {
+            return line;
+        }
+
+        // the bar only has one line
+        let text = text.replace(['\n', '\r'], " ");
+        let bidi = BidiInfo::new(&text, None);
+
+        for paragraph in &bidi.paragraphs {
+            let (levels, runs) = bidi.visual_runs(paragraph, paragraph.range.clone());
+            for run in runs {
+                let rtl = levels[run.start].is_rtl();
+                let mut segments = self.font_segments(&text[run.clone()]);
+                // the segments are in logical order, right-to-left runs are drawn backwards
+                if rtl {
+                    segments.reverse();
+                }
+
+                for (face, segment) in segments {
+                    self.shape_segment(&mut line, face, segment, rtl);
+                }
+            }
+        }
+
+        line
+    }
+
+    /// splits the text where it needs another font, like dwm does with its fallback fonts.
+    /// spaces, punctuation and combining marks stay with the font before them,
+    /// so the letters around them can still join
+    fn font_segments<'t>(&self, text: &'t str) -> Vec<(usize, &'t str)> {
+        let mut segments: Vec<(usize, &'t str)> = Vec::new();
+        let mut start = 0;
+        let mut current: Option<usize> = None;
+
+        for (index, c) in text.char_indices() {
+            let face = match current {
+                Some(face) if !c.is_alphanumeric() && self.faces[face].has_glyph(c) => face,
+                _ => self.face_for(c),
+            };
+
+            if let Some(previous) = current.filter(|&previous| previous != face) {
+                segments.push((previous, &text[start..index]));
+                start = index;
+            }
+            current = Some(face);
+        }
+
+        if let Some(face) = current {
+            segments.push((face, &text[start..]));
+        }
+
+        segments
+    }
+
+    /// the first font that has the character, the main font draws the missing glyph otherwise
+    fn face_for(&self, c: char) -> usize {
+        self.faces
+            .iter()
+            .position(|face| face.has_glyph(c))
+            .unwrap_or(0)
+    }
+
+    fn shape_segment(&self, line: &mut Line, face_index: usize, text: &str, rtl: bool) {
+        let face = &self.faces[face_index];
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str(text);
+        buffer.set_direction(if rtl {
+            Direction::RightToLeft
+        } else {
+            Direction::LeftToRight
+        });
+        buffer.guess_segment_properties();
+
+        let shaped = rustybuzz::shape(&face.shaper, &[], buffer);
+        for (info, position) in shaped.glyph_infos().iter().zip(shaped.glyph_positions()) {
+            line.glyphs.push(PlacedGlyph {
+                face: face_index,
+                id: GlyphId(info.glyph_id as u16),
+                x: line.width + position.x_offset as f32 * face.scale_factor,
+                y: position.y_offset as f32 * face.scale_factor,
+            });
+            line.width += position.x_advance as f32 * face.scale_factor;
+        }
+    }
+}
+
+/// the names fontconfig uses for the generic families, anything else is a family name
+fn family(name: &str) -> Family<'_> {
+    match name {
+        "monospace" => Family::Monospace,
+        "sans-serif" | "sans" => Family::SansSerif,
+        "serif" => Family::Serif,
+        _ => Family::Name(name),
+    }
+}
+
+fn load_face(db: &Database, family: Family, size: f32) -> Option<Face> {
+    let id = db
+        .query(&Query {
+            families: &[family],
+            ..Query::default()
+        })
+        .or_else(|| generic_fallback(db, family))?;
+
+    face_from_db(db, id, size)
+}
+
+/// fontdb only knows the first font fontconfig prefers for the generic families,
+/// which often isn't installed, so any regular face of the right kind will do
+fn generic_fallback(db: &Database, family: Family) -> Option<fontdb::ID> {
+    if matches!(family, Family::Name(_)) {
+        return None;
+    }
+
+    db.faces()
+        .find(|info| {
+            info.style == Style::Normal
+                && info.weight == Weight::NORMAL
+                && (family != Family::Monospace || info.monospaced)
+        })
+        .map(|info| info.id)
+}
+
+fn face_from_db(db: &Database, id: fontdb::ID, size: f32) -> Option<Face> {
+    let (data, index) = db.with_face_data(id, |data, index| (data.to_vec(), index))?;
+    // SAFETY: the heap buffer of the Vec doesn't move with the Face, is never changed,
+    // and outlives the faces that borrow it because `_data` is dropped last
+    let slice: &'static [u8] = unsafe { std::slice::from_raw_parts(data.as_ptr(), data.len()) };
+    let name = || db.face(id).map(|info| info.post_script_name.clone());
+
+    let font = match FontRef::try_from_slice_and_index(slice, index) {
+        Ok(font) => font,
+        Err(err) => {
+            tracing::warn!("failed to load font {}: {err}", name().unwrap_or_default());
+            return None;
+        }
+    };
+    let Some(shaper) = rustybuzz::Face::from_slice(slice, index) else {
+        tracing::warn!(
+            "failed to load font {} for shaping",
+            name().unwrap_or_default()
+        );
+        return None;
+    };
+    let units_per_em = font.units_per_em()?;
+
+    Some(Face {
+        font,
+        shaper,
+        scale_factor: size / units_per_em,
+        _data: data,
+    })
+}
+
+fn rgb(color: &RGBA) -> u32 {
+    color.as_argb_u32() & 0x00ff_ffff
+}
+
+/// mixes the foreground into the background by the coverage of the glyph
+fn blend(background: u32, foreground: u32, coverage: f32) -> u32 {
+    let coverage = coverage.clamp(0.0, 1.0);
+    let channel = |shift: u32| {
+        let bg = ((background >> shift) & 0xff) as f32;
+        let fg = ((foreground >> shift) & 0xff) as f32;
+        ((bg + (fg - bg) * coverage).round() as u32) << shift
+    };
+
+    channel(16) | channel(8) | channel(0)
+}
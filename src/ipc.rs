@@ -0,0 +1,456 @@
+//! the control socket used by `bunnuc` and scripts to drive the window manager.
+//!
+//! the protocol is newline delimited JSON, every line sent to the socket is a [`Request`]
+//! and gets exactly one [`Response`] line back.
+//! after a [`Request::Subscribe`] the connection only receives [`Event`] lines.
+
+use std::{
+    io::{self, Read, Write},
+    os::unix::{
+        io::{AsRawFd, RawFd},
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::WMCommand;
+
+/// overrides the socket path, it's also set by the window manager for the programs it starts
+pub const SOCKET_ENV: &str = "BUNNUAFETH_SOCKET";
+
+/// clients that send more than this without ending the line are dropped
+const MAX_REQUEST_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Request {
+    /// runs a command, as if its binding was pressed
+    Command(WMCommand),
+    Query(Query),
+    /// turns the connection into an event stream, an empty list subscribes to every event
+    Subscribe(Vec<EventKind>),
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Query {
+    Windows,
+    Focused,
+    Layout,
+    Tags,
+    Monitors,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Response {
+    Ok(serde_json::Value),
+    Error(String),
+}
+
+/// pushed to subscribed clients when something changes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// `window` is `None` when nothing is focused
+    Focus {
+        window: Option<u32>,
+    },
+    Map {
+        window: u32,
+    },
+    Unmap {
+        window: u32,
+    },
+    Title {
+        window: u32,
+        title: String,
+    },
+    Layout {
+        monitor: usize,
+        layout: String,
+    },
+    Fullscreen {
+        window: u32,
+        fullscreen: bool,
+    },
+    /// the viewed tags of a monitor changed
+    Tags {
+        monitor: usize,
+        tags: u32,
+    },
+    /// a window started or stopped demanding attention
+    Urgent {
+        window: u32,
+        urgent: bool,
+    },
+    /// a window didn't answer `_NET_WM_PING` in time, or answered again
+    Hung {
+        window: u32,
+        hung: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Focus,
+    Map,
+    Unmap,
+    Title,
+    Layout,
+    Fullscreen,
+    Tags,
+    Urgent,
+    Hung,
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::Focus { .. } => EventKind::Focus,
+            Event::Map { .. } => EventKind::Map,
+            Event::Unmap { .. } => EventKind::Unmap,
+            Event::Title { .. } => EventKind::Title,
+            Event::Layout { .. } => EventKind::Layout,
+            Event::Fullscreen { .. } => EventKind::Fullscreen,
+            Event::Tags { .. } => EventKind::Tags,
+            Event::Urgent { .. } => EventKind::Urgent,
+            Event::Hung { .. } => EventKind::Hung,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct WindowInfo {
+    pub id: u32,
+    pub title: String,
+    pub instance: String,
+    pub class: String,
+    pub monitor: usize,
+    pub tags: u32,
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+    pub floating: bool,
+    pub fullscreen: bool,
+    pub focused: bool,
+    pub urgent: bool,
+    pub hung: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LayoutInfo {
+    pub monitor: usize,
+    pub layout: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TagInfo {
+    pub index: usize,
+    pub name: String,
+    pub selected: bool,
+    pub occupied: bool,
+    /// a window on the tag demands attention
+    pub urgent: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MonitorInfo {
+    pub index: usize,
+    pub output: String,
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+    pub focused: bool,
+    pub layout: &'static str,
+    pub tags: u32,
+}
+
+/// `$BUNNUAFETH_SOCKET`, or a socket for the current display
+/// in `$XDG_RUNTIME_DIR` (falling back to `/tmp`)
+pub fn socket_path() -> PathBuf {
+    if let Some(path) = std::env::var_os(SOCKET_ENV).filter(|p| !p.is_empty()) {
+        return PathBuf::from(path);
+    }
+
+    let display = std::env::var("DISPLAY")
+        .unwrap_or_default()
+        .replace('/', "_");
+
+    match std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir).join(format!("bunnuafeth{display}.sock")),
+        None => {
+            // SAFETY: getuid can't fail
+            let uid = unsafe { libc::getuid() };
+            PathBuf::from(format!("/tmp/bunnuafeth-{uid}{display}.sock"))
+        }
+    }
+}
+
+/// identifies a client connected to the [`IpcServer`]
+pub type ClientId = u64;
+
+struct Client {
+    id: ClientId,
+    stream: UnixStream,
+    buffer: Vec<u8>,
+    /// the events the client is subscribed to, `None` if it didn't subscribe
+    subscription: Option<Vec<EventKind>>,
+}
+
+impl Client {
+    fn is_subscribed_to(&self, kind: EventKind) -> bool {
+        self.subscription
+            .as_ref()
+            .is_some_and(|kinds| kinds.is_empty() || kinds.contains(&kind))
+    }
+}
+
+pub struct IpcServer {
+    listener: UnixListener,
+    path: PathBuf,
+    clients: Vec<Client>,
+    next_id: ClientId,
+}
+
+impl IpcServer {
+    pub fn bind(path: &Path) -> io::Result<Self> {
+        if path.exists() {
+            if UnixStream::connect(path).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("{} is used by another instance", path.display()),
+                ));
+            }
+
+            // left behind by an instance that didn't exit cleanly
+            std::fs::remove_file(path)?;
+        }
+
+        let listener = UnixListener::bind(path)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self {
+            listener,
+            path: path.to_path_buf(),
+            clients: Vec::new(),
+            next_id: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// the listener and every connected client, to wait on alongside the X connection
+    pub fn fds(&self) -> Vec<RawFd> {
+        std::iter::once(self.listener.as_raw_fd())
+            .chain(self.clients.iter().map(|c| c.stream.as_raw_fd()))
+            .collect()
+    }
+
+    /// accepts new clients and returns every complete request they sent,
+    /// clients that disconnected or sent a line that is too long are dropped
+    pub fn read_requests(&mut self) -> Vec<(ClientId, Result<Request, String>)> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(e) = stream.set_nonblocking(true) {
+                        tracing::warn!("failed to set up IPC client: {e}");
+                        continue;
+                    }
+                    self.clients.push(Client {
+                        id: self.next_id,
+                        stream,
+                        buffer: Vec::new(),
+                        subscription: None,
+                    });
+                    self.next_id += 1;
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    tracing::warn!("failed to accept IPC client: {e}");
+                    break;
+                }
+            }
+        }
+
+        let mut requests = Vec::new();
+
+        self.clients.retain_mut(|client| {
+            let mut open = true;
+            let mut chunk = [0; 4096];
+            loop {
+                match client.stream.read(&mut chunk) {
+                    Ok(0) => {
+                        open = false;
+                        break;
+                    }
+                    Ok(n) => {
+                        client.buffer.extend_from_slice(&chunk[..n]);
+                        // the rest is read on the next round, after the complete lines are handled
+                        if client.buffer.len() > MAX_REQUEST_SIZE {
+                            break;
+                        }
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => {
+                        tracing::debug!("IPC client {} read failed: {e}", client.id);
+                        open = false;
+                        break;
+                    }
+                }
+            }
+
+            while let Some(end) = client.buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = client.buffer.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+
+                let request = serde_json::from_str(line).map_err(|e| e.to_string());
+                requests.push((client.id, request));
+            }
+
+            if client.buffer.len() > MAX_REQUEST_SIZE {
+                tracing::debug!("dropping IPC client {}: request too long", client.id);
+                return false;
+            }
+
+            open
+        });
+
+        requests
+    }
+
+    /// sends a response to a client, clients that can't receive it right away are dropped
+    pub fn respond(&mut self, client_id: ClientId, response: &Response) {
+        let Some(index) = self.clients.iter().position(|c| c.id == client_id) else {
+            return;
+        };
+
+        if let Err(e) = write_line(&mut self.clients[index].stream, response) {
+            tracing::debug!("dropping IPC client {client_id}: {e}");
+            self.clients.swap_remove(index);
+        }
+    }
+
+    /// starts sending events of the provided kinds to a client
+    pub fn subscribe(&mut self, client_id: ClientId, kinds: Vec<EventKind>) {
+        if let Some(client) = self.clients.iter_mut().find(|c| c.id == client_id) {
+            client.subscription = Some(kinds);
+        }
+    }
+
+    pub fn has_subscribers(&self, kind: EventKind) -> bool {
+        self.clients.iter().any(|c| c.is_subscribed_to(kind))
+    }
+
+    /// sends an event to every client subscribed to it,
+    /// clients that can't receive it right away are dropped
+    pub fn emit(&mut self, event: &Event) {
+        let kind = event.kind();
+        self.clients.retain_mut(|client| {
+            if !client.is_subscribed_to(kind) {
+                return true;
+            }
+
+            match write_line(&mut client.stream, event) {
+                Ok(()) => true,
+                Err(e) => {
+                    tracing::debug!("dropping IPC subscriber {}: {e}", client.id);
+                    false
+                }
+            }
+        });
+    }
+}
+
+impl Drop for IpcServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn write_line<T: Serialize>(stream: &mut UnixStream, value: &T) -> io::Result<()> {
+    let mut line = serde_json::to_vec(value)?;
+    line.push(b'\n');
+
+    // the socket stays non-blocking, waiting for a client that doesn't read
+    // would stop the window manager, so writes that would block fail instead
+    stream.write_all(&line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server(name: &str) -> IpcServer {
+        let path = std::env::temp_dir().join(format!(
+            "bunnuafeth-test-{}-{name}.sock",
+            std::process::id()
+        ));
+        IpcServer::bind(&path).unwrap()
+    }
+
+    #[test]
+    fn reads_complete_lines() {
+        let mut server = server("lines");
+        let mut client = UnixStream::connect(server.path()).unwrap();
+        client
+            .write_all(b"{\"query\":\"tags\"}\n\n{\"query\":\"lay")
+            .unwrap();
+
+        let requests = server.read_requests();
+        assert_eq!(requests.len(), 1);
+        assert!(matches!(requests[0].1, Ok(Request::Query(Query::Tags))));
+
+        client.write_all(b"out\"}\n").unwrap();
+        let requests = server.read_requests();
+        assert_eq!(requests.len(), 1);
+        assert!(matches!(requests[0].1, Ok(Request::Query(Query::Layout))));
+    }
+
+    #[test]
+    fn drops_clients_with_long_lines() {
+        let mut server = server("long");
+        let mut client = UnixStream::connect(server.path()).unwrap();
+        client.write_all(&vec![b'a'; MAX_REQUEST_SIZE + 1]).unwrap();
+
+        assert!(server.read_requests().is_empty());
+        // only the listener is left
+        assert_eq!(server.fds().len(), 1);
+    }
+
+    #[test]
+    fn drops_subscribers_that_dont_read() {
+        let mut server = server("stalled");
+        let mut client = UnixStream::connect(server.path()).unwrap();
+        client.write_all(b"{\"subscribe\":[]}\n").unwrap();
+
+        let (id, _) = server.read_requests().remove(0);
+        server.subscribe(id, Vec::new());
+
+        let event = Event::Title {
+            window: 1,
+            title: "a".repeat(1024),
+        };
+        // the client never reads, so its socket fills up instead of blocking the server
+        for _ in 0..100_000 {
+            if !server.has_subscribers(EventKind::Title) {
+                break;
+            }
+            server.emit(&event);
+        }
+
+        assert!(!server.has_subscribers(EventKind::Title));
+    }
+}
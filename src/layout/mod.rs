@@ -1,17 +1,106 @@
 use crate::some_if_changed;
+use serde::{Deserialize, Serialize};
 use x11rb::protocol::xproto::Window;
 
-use crate::{wm::BORDER_WIDTH, WindowProperties, WindowState, WindowType};
+use crate::{wm::BORDER_WIDTH, BoundingBox, WindowProperties, WindowState, WindowType};
 
+mod tiled;
+
+pub use tiled::{Gaps, DEFAULT_MAIN_COUNT, DEFAULT_MAIN_FACTOR};
+
+/// the main area can't take the whole screen or disappear completely
+pub const MAIN_FACTOR_RANGE: std::ops::RangeInclusive<f32> = 0.05..=0.95;
+/// how much bigger or smaller than its neighbours a tiled window can be, like dwm's `cfact`
+pub const SIZE_WEIGHT_RANGE: std::ops::RangeInclusive<f32> = 0.25..=4.0;
+
+/// layouts are written as their name in the config and over IPC, like `"monocle"`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "&'static str")]
 pub enum Layout {
     Floating,
     Tiled(TiledLayout),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TiledLayout {
     MainStack,
+    BottomStack,
+    Monocle,
+    CenteredMaster,
+    ThreeColumn,
+    Grid,
+    Spiral,
 }
 
+impl Layout {
+    /// every layout, in the order they are cycled through
+    pub const ALL: [Layout; 8] = [
+        Layout::Tiled(TiledLayout::MainStack),
+        Layout::Tiled(TiledLayout::BottomStack),
+        Layout::Tiled(TiledLayout::CenteredMaster),
+        Layout::Tiled(TiledLayout::ThreeColumn),
+        Layout::Tiled(TiledLayout::Grid),
+        Layout::Tiled(TiledLayout::Spiral),
+        Layout::Tiled(TiledLayout::Monocle),
+        Layout::Floating,
+    ];
+
+    /// the name used to refer to the layout in the config and over IPC
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layout::Floating => "floating",
+            Layout::Tiled(TiledLayout::MainStack) => "main_stack",
+            Layout::Tiled(TiledLayout::BottomStack) => "bottom_stack",
+            Layout::Tiled(TiledLayout::Monocle) => "monocle",
+            Layout::Tiled(TiledLayout::CenteredMaster) => "centered_master",
+            Layout::Tiled(TiledLayout::ThreeColumn) => "three_column",
+            Layout::Tiled(TiledLayout::Grid) => "grid",
+            Layout::Tiled(TiledLayout::Spiral) => "spiral",
+        }
+    }
+
+    /// a short symbol for the bar, like dwm's
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Layout::Floating => "><>",
+            Layout::Tiled(TiledLayout::MainStack) => "[]=",
+            Layout::Tiled(TiledLayout::BottomStack) => "TTT",
+            Layout::Tiled(TiledLayout::Monocle) => "[M]",
+            Layout::Tiled(TiledLayout::CenteredMaster) => "|M|",
+            Layout::Tiled(TiledLayout::ThreeColumn) => "|||",
+            Layout::Tiled(TiledLayout::Grid) => "###",
+            Layout::Tiled(TiledLayout::Spiral) => "[@]",
+        }
+    }
+
+    /// the layout `offset` places away from this one in [`Layout::ALL`], wrapping around
+    pub fn cycle(&self, offset: isize) -> Layout {
+        let index = Layout::ALL.iter().position(|l| l == self).unwrap_or(0) as isize;
+        let len = Layout::ALL.len() as isize;
+
+        Layout::ALL[(index + offset).rem_euclid(len) as usize]
+    }
+}
+
+impl TryFrom<String> for Layout {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        Layout::ALL
+            .into_iter()
+            .find(|l| l.name() == name)
+            .ok_or_else(|| format!("unknown layout `{name}`"))
+    }
+}
+
+impl From<Layout> for &'static str {
+    fn from(layout: Layout) -> Self {
+        layout.name()
+    }
+}
+
+/// the space a dock reserves along one edge of the screen,
+/// `start` and `end` are the first and last pixel of the edge it covers
 #[derive(Debug, Default, Clone, PartialEq)]
 pub struct EdgeDimensions {
     pub width: u32,
@@ -19,6 +108,25 @@ pub struct EdgeDimensions {
     pub end: u32,
 }
 
+impl EdgeDimensions {
+    /// a strut along the whole edge, like the ones from `_NET_WM_STRUT`
+    pub fn whole_edge(width: u32) -> Self {
+        Self {
+            width,
+            start: 0,
+            end: u32::MAX,
+        }
+    }
+
+    /// whether the strut covers any of the `length` pixels from `offset` along its edge
+    fn overlaps(&self, offset: i16, length: u16) -> bool {
+        let first = offset.max(0) as u32;
+        let last = (offset as i32 + length as i32 - 1).max(0) as u32;
+
+        self.start <= last && self.end >= first
+    }
+}
+
 #[derive(Debug, Default, Clone, PartialEq)]
 pub struct ReservedEdges {
     pub top: EdgeDimensions,
@@ -27,9 +135,129 @@ pub struct ReservedEdges {
     pub bottom: EdgeDimensions,
 }
 
+impl ReservedEdges {
+    /// parses `_NET_WM_STRUT_PARTIAL`: the widths of the left, right, top and bottom edges,
+    /// then the start and end of each of them in the same order
+    pub fn from_strut_partial(values: &[u32]) -> Option<Self> {
+        let values: &[u32; 12] = values.get(..12)?.try_into().ok()?;
+        let edge = |width: u32, start: u32, end: u32| {
+            // some docks only fill in the widths
+            if start == 0 && end == 0 {
+                EdgeDimensions::whole_edge(width)
+            } else {
+                EdgeDimensions { width, start, end }
+            }
+        };
+
+        Some(ReservedEdges {
+            left: edge(values[0], values[4], values[5]),
+            right: edge(values[1], values[6], values[7]),
+            top: edge(values[2], values[8], values[9]),
+            bottom: edge(values[3], values[10], values[11]),
+        })
+    }
+
+    /// parses `_NET_WM_STRUT`, the older property where every strut spans its whole edge
+    pub fn from_strut(values: &[u32]) -> Option<Self> {
+        let values: &[u32; 4] = values.get(..4)?.try_into().ok()?;
+
+        Some(ReservedEdges {
+            left: EdgeDimensions::whole_edge(values[0]),
+            right: EdgeDimensions::whole_edge(values[1]),
+            top: EdgeDimensions::whole_edge(values[2]),
+            bottom: EdgeDimensions::whole_edge(values[3]),
+        })
+    }
+
+    /// the largest strut of both on every edge
+    pub fn max(&self, other: &ReservedEdges) -> ReservedEdges {
+        let max = |a: &EdgeDimensions, b: &EdgeDimensions| EdgeDimensions {
+            width: a.width.max(b.width),
+            start: a.start.min(b.start),
+            end: a.end.max(b.end),
+        };
+
+        ReservedEdges {
+            top: max(&self.top, &other.top),
+            right: max(&self.right, &other.right),
+            left: max(&self.left, &other.left),
+            bottom: max(&self.bottom, &other.bottom),
+        }
+    }
+
+    /// struts are measured from the edges of the screen,
+    /// this converts them to be measured from the edges of the provided monitor area.
+    /// partial struts that don't cover the monitor along their edge don't reserve anything on it,
+    /// and neither do struts that reach across the whole monitor, their dock is on a monitor
+    /// further in, like a bar at the top of the lower one of two stacked monitors
+    pub fn relative_to(
+        &self,
+        area: &BoundingBox,
+        screen_width: u16,
+        screen_height: u16,
+    ) -> ReservedEdges {
+        let left_offset = area.x.max(0) as u32;
+        let top_offset = area.y.max(0) as u32;
+        let right_offset = (screen_width as i32 - area.x as i32 - area.width as i32).max(0) as u32;
+        let bottom_offset =
+            (screen_height as i32 - area.y as i32 - area.height as i32).max(0) as u32;
+        let relative = |edge: &EdgeDimensions, offset: u32, along: (i16, u16), across: u16| {
+            let width = edge.width.saturating_sub(offset);
+            EdgeDimensions {
+                width: if edge.overlaps(along.0, along.1) && width < across as u32 {
+                    width
+                } else {
+                    0
+                },
+                ..edge.clone()
+            }
+        };
+
+        ReservedEdges {
+            top: relative(&self.top, top_offset, (area.x, area.width), area.height),
+            right: relative(&self.right, right_offset, (area.y, area.height), area.width),
+            left: relative(&self.left, left_offset, (area.y, area.height), area.width),
+            bottom: relative(
+                &self.bottom,
+                bottom_offset,
+                (area.x, area.width),
+                area.height,
+            ),
+        }
+    }
+}
+
 pub struct LayoutManager {
     pub layout: Layout,
     pub reserved: ReservedEdges,
+    pub border_width: u32,
+    /// the share of the monitor given to the main windows, dwm's `mfact`
+    pub main_factor: f32,
+    /// how many windows are in the main area, dwm's `nmaster`
+    pub main_count: usize,
+    pub gaps: Gaps,
+    /// turns the gaps off without forgetting their size
+    pub gaps_enabled: bool,
+    /// no gaps when there is only one tiled window
+    pub smart_gaps: bool,
+    /// whether tiled windows are sized according to their `WM_NORMAL_HINTS` too, like dwm's `resizehints`
+    pub size_hints: bool,
+}
+
+impl Default for LayoutManager {
+    fn default() -> Self {
+        Self {
+            layout: Layout::Tiled(TiledLayout::MainStack),
+            reserved: ReservedEdges::default(),
+            border_width: BORDER_WIDTH,
+            main_factor: DEFAULT_MAIN_FACTOR,
+            main_count: DEFAULT_MAIN_COUNT,
+            gaps: Gaps::default(),
+            gaps_enabled: true,
+            smart_gaps: true,
+            size_hints: false,
+        }
+    }
 }
 
 #[derive(Default, Debug)]
@@ -45,94 +273,121 @@ pub struct WindowStateDiff {
 }
 
 impl LayoutManager {
+    /// grows (or shrinks with a negative `delta`) the main area, returns false if nothing changed
+    pub fn change_main_factor(&mut self, delta: f32) -> bool {
+        let factor =
+            (self.main_factor + delta).clamp(*MAIN_FACTOR_RANGE.start(), *MAIN_FACTOR_RANGE.end());
+        if factor == self.main_factor {
+            return false;
+        }
+
+        self.main_factor = factor;
+        true
+    }
+
+    /// changes the inner and outer gaps by the provided amounts, returns false if nothing changed
+    pub fn change_gaps(&mut self, inner_delta: i32, outer_delta: i32) -> bool {
+        let gaps = Gaps {
+            inner: self.gaps.inner.saturating_add_signed(inner_delta),
+            outer: self.gaps.outer.saturating_add_signed(outer_delta),
+        };
+        if gaps.inner == self.gaps.inner && gaps.outer == self.gaps.outer {
+            return false;
+        }
+
+        self.gaps = gaps;
+        // changing the size of disabled gaps would do nothing visible
+        self.gaps_enabled = true;
+        true
+    }
+
+    /// adds (or removes with a negative `delta`) main slots, returns false if nothing changed
+    pub fn change_main_count(&mut self, delta: isize) -> bool {
+        let count = self.main_count.saturating_add_signed(delta);
+        if count == self.main_count {
+            return false;
+        }
+
+        self.main_count = count;
+        true
+    }
+
+    /// the part of the monitor area that is not reserved by docks,
+    /// it's empty when the docks reserve more than the whole area
+    pub fn usable_area(&self, area: &BoundingBox) -> BoundingBox {
+        let clamp = |width: u32, length: u16| width.min(length as u32) as u16;
+        let left = clamp(self.reserved.left.width, area.width);
+        let top = clamp(self.reserved.top.width, area.height);
+        let right = clamp(self.reserved.right.width, area.width);
+        let bottom = clamp(self.reserved.bottom.width, area.height);
+
+        BoundingBox::new(
+            area.x.saturating_add_unsigned(left),
+            area.y.saturating_add_unsigned(top),
+            area.width.saturating_sub(left).saturating_sub(right),
+            area.height.saturating_sub(top).saturating_sub(bottom),
+        )
+    }
+
     pub fn calculate_dimensions(
         &self,
         windows: Vec<&WindowState>,
-        screen_width: u16,
-        screen_height: u16,
+        monitor_area: &BoundingBox,
     ) -> Option<Vec<WindowStateDiff>> {
-        match &self.layout {
-            Layout::Floating => None,
-            Layout::Tiled(tiled_layout) => match tiled_layout {
-                TiledLayout::MainStack => {
-                    let mut windows: Vec<WindowState> = windows
-                        .into_iter()
-                        .cloned()
-                        .filter(|w| w.r#type == WindowType::Normal && !w.is_floating)
-                        .collect();
-                    let mut windows_final: Vec<WindowStateDiff> = Vec::new();
-                    let screen_height = screen_height
-                        - self.reserved.top.width as u16
-                        - self.reserved.bottom.width as u16;
-
-                    if let Some((main, windows)) = windows.split_first_mut() {
-                        let sub_windows_count = windows.len();
-                        let mut main_diff = WindowStateDiff {
-                            window: main.window,
-                            ..Default::default()
-                        };
-
-                        main_diff.height = some_if_changed!(
-                            main.height,
-                            screen_height - (BORDER_WIDTH * 2) as u16
-                        );
-
-                        main_diff.width = some_if_changed!(
-                            main.width,
-                            screen_width
-                                - (BORDER_WIDTH * 2) as u16
-                                - self.reserved.right.width as u16
-                                - self.reserved.left.width as u16
-                        );
-
-                        // FIXME: deal with the insane amount of casts
-
-                        main_diff.y = some_if_changed!(main.y, self.reserved.top.width as i16);
-                        main_diff.x = some_if_changed!(main.x, self.reserved.left.width as i16);
-
-                        let mut sub_windows = Vec::new();
-
-                        if sub_windows_count >= 1 {
-                            main_diff.width = some_if_changed!(
-                                main.width,
-                                (screen_width / 2) - (BORDER_WIDTH * 2) as u16
-                            );
-
-                            sub_windows = windows
-                                .iter_mut()
-                                .enumerate()
-                                .map(|(i, win_state)| {
-                                    let height = screen_height / sub_windows_count as u16
-                                        - (BORDER_WIDTH * 2) as u16;
-
-                                    WindowStateDiff {
-                                        x: some_if_changed!(win_state.x, (screen_width / 2) as i16),
-                                        y: some_if_changed!(
-                                            win_state.y,
-                                            (((height + (BORDER_WIDTH * 2) as u16) * i as u16)
-                                                + (self.reserved.top.width as u16))
-                                                as i16
-                                        ),
-                                        width: some_if_changed!(
-                                            win_state.width,
-                                            (screen_width / 2) - (BORDER_WIDTH * 2) as u16
-                                        ),
-                                        height: some_if_changed!(win_state.height, height),
-                                        window: win_state.window,
-                                        ..Default::default()
-                                    }
-                                })
-                                .collect();
-                        }
-                        windows_final.push(main_diff);
-                        windows_final.extend(sub_windows);
-
-                        return Some(windows_final);
-                    }
-                    return None;
-                }
-            },
+        let Layout::Tiled(tiled_layout) = &self.layout else {
+            return None;
+        };
+
+        let windows: Vec<&WindowState> = windows
+            .into_iter()
+            .filter(|w| {
+                w.r#type == WindowType::Normal && !w.is_floating && !w.properties.is_fullscreen
+            })
+            .collect();
+        if windows.is_empty() {
+            return None;
         }
+
+        let gaps = if !self.gaps_enabled || (self.smart_gaps && windows.len() == 1) {
+            Gaps::default()
+        } else {
+            self.gaps
+        };
+
+        let weights: Vec<f32> = windows.iter().map(|w| w.size_weight).collect();
+        let area = self.usable_area(monitor_area);
+        let rects = tiled::arrange(
+            tiled_layout,
+            &area,
+            &weights,
+            self.main_factor,
+            self.main_count,
+            gaps,
+        );
+
+        let border = (self.border_width * 2) as u16;
+        let windows_final = windows
+            .into_iter()
+            .zip(rects)
+            .map(|(win_state, rect)| {
+                let mut width = rect.width.saturating_sub(border).max(1);
+                let mut height = rect.height.saturating_sub(border).max(1);
+                if self.size_hints {
+                    (width, height) = win_state.size_hints.apply(width, height);
+                }
+
+                WindowStateDiff {
+                    x: some_if_changed!(win_state.x, rect.x),
+                    y: some_if_changed!(win_state.y, rect.y),
+                    width: some_if_changed!(win_state.width, width),
+                    height: some_if_changed!(win_state.height, height),
+                    window: win_state.window,
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        Some(windows_final)
     }
 }
 
@@ -146,3 +401,143 @@ macro_rules! some_if_changed {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// two 1920x1080 monitors, one above the other
+    fn stacked() -> (BoundingBox, BoundingBox) {
+        (
+            BoundingBox::new(0, 0, 1920, 1080),
+            BoundingBox::new(0, 1080, 1920, 1080),
+        )
+    }
+
+    fn strut_partial(values: [u32; 12]) -> ReservedEdges {
+        ReservedEdges::from_strut_partial(&values).unwrap()
+    }
+
+    #[test]
+    fn strut_partial_without_range_covers_whole_edge() {
+        let reserved = strut_partial([0, 0, 30, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+
+        assert_eq!(reserved.top, EdgeDimensions::whole_edge(30));
+        assert_eq!(reserved.bottom, EdgeDimensions::whole_edge(0));
+    }
+
+    #[test]
+    fn strut_needs_every_value() {
+        assert_eq!(ReservedEdges::from_strut_partial(&[0; 11]), None);
+        assert_eq!(ReservedEdges::from_strut(&[0; 3]), None);
+        assert_eq!(
+            ReservedEdges::from_strut(&[1, 2, 3, 4]).unwrap().bottom,
+            EdgeDimensions::whole_edge(4)
+        );
+    }
+
+    #[test]
+    fn strut_relative_to_stacked_monitors() {
+        let (upper, lower) = stacked();
+        // a 30px bar at the top of the lower monitor
+        let reserved = strut_partial([0, 0, 1110, 0, 0, 0, 0, 0, 0, 1919, 0, 0]);
+
+        assert_eq!(reserved.relative_to(&lower, 1920, 2160).top.width, 30);
+        assert_eq!(reserved.relative_to(&upper, 1920, 2160).top.width, 0);
+    }
+
+    #[test]
+    fn strut_across_whole_monitor_reserves_nothing() {
+        let (upper, lower) = stacked();
+        // a 30px bar at the bottom of the upper monitor
+        let reserved = strut_partial([0, 0, 0, 1110, 0, 0, 0, 0, 0, 0, 0, 1919]);
+
+        assert_eq!(reserved.relative_to(&upper, 1920, 2160).bottom.width, 30);
+        assert_eq!(reserved.relative_to(&lower, 1920, 2160).bottom.width, 0);
+
+        let huge = ReservedEdges::from_strut(&[5000, 5000, 0, 0]).unwrap();
+        let relative = huge.relative_to(&upper, 1920, 2160);
+        assert_eq!(relative.left.width, 0);
+        assert_eq!(relative.right.width, 0);
+    }
+
+    #[test]
+    fn strut_relative_to_side_by_side_monitors() {
+        let left = BoundingBox::new(0, 0, 1920, 1080);
+        let right = BoundingBox::new(1920, 0, 1280, 1024);
+        // a panel along the top of the right monitor only
+        let reserved = strut_partial([0, 0, 24, 0, 0, 0, 0, 0, 1920, 3199, 0, 0]);
+
+        assert_eq!(reserved.relative_to(&left, 3200, 1080).top.width, 0);
+        assert_eq!(reserved.relative_to(&right, 3200, 1080).top.width, 24);
+
+        // the bottom of the smaller monitor is 56px above the bottom of the screen
+        let bottom = ReservedEdges::from_strut(&[0, 0, 0, 80]).unwrap();
+        assert_eq!(bottom.relative_to(&right, 3200, 1080).bottom.width, 24);
+        assert_eq!(bottom.relative_to(&left, 3200, 1080).bottom.width, 80);
+    }
+
+    #[test]
+    fn usable_area_leaves_out_reserved_edges() {
+        let manager = LayoutManager {
+            reserved: ReservedEdges::from_strut(&[10, 20, 30, 40]).unwrap(),
+            ..LayoutManager::default()
+        };
+        let area = BoundingBox::new(1920, 0, 1280, 1024);
+
+        assert_eq!(
+            manager.usable_area(&area),
+            BoundingBox::new(1930, 30, 1250, 954)
+        );
+    }
+
+    #[test]
+    fn usable_area_stays_inside_monitor() {
+        let area = BoundingBox::new(0, 1080, 1920, 1080);
+        let manager = LayoutManager {
+            reserved: ReservedEdges::from_strut(&[0, 0, 1110, 1110]).unwrap(),
+            ..LayoutManager::default()
+        };
+        assert_eq!(
+            manager.usable_area(&area),
+            BoundingBox::new(0, 2160, 1920, 0)
+        );
+
+        let manager = LayoutManager {
+            reserved: ReservedEdges::from_strut(&[u32::MAX, u32::MAX, 0, 0]).unwrap(),
+            ..LayoutManager::default()
+        };
+        assert_eq!(
+            manager.usable_area(&area),
+            BoundingBox::new(1920, 1080, 0, 1080)
+        );
+    }
+
+    #[test]
+    fn layout_names_round_trip() {
+        for layout in Layout::ALL {
+            assert_eq!(Layout::try_from(layout.name().to_string()), Ok(layout));
+        }
+        assert!(Layout::try_from("tabbed".to_string()).is_err());
+    }
+
+    #[test]
+    fn layout_cycle_wraps_around() {
+        let first = Layout::ALL[0];
+        let last = Layout::ALL[Layout::ALL.len() - 1];
+
+        assert_eq!(first.cycle(-1), last);
+        assert_eq!(last.cycle(1), first);
+        assert_eq!(first.cycle(Layout::ALL.len() as isize), first);
+    }
+
+    #[test]
+    fn largest_strut_wins() {
+        let a = ReservedEdges::from_strut(&[10, 0, 30, 0]).unwrap();
+        let b = ReservedEdges::from_strut(&[20, 0, 5, 0]).unwrap();
+        let reserved = a.max(&b);
+
+        assert_eq!(reserved.left.width, 20);
+        assert_eq!(reserved.top.width, 30);
+    }
+}
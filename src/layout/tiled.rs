@@ -0,0 +1,528 @@
+//! the geometry of the tiled layouts.
+//!
+//! every function returns the outer rectangle (borders included) of `count` windows
+//! placed in `area`, in the same order as the windows.
+
+use crate::BoundingBox;
+
+use super::TiledLayout;
+
+/// the share of the area given to the main windows
+pub const DEFAULT_MAIN_FACTOR: f32 = 0.5;
+/// how many windows are in the main area
+pub const DEFAULT_MAIN_COUNT: usize = 1;
+
+/// an area in `i32` so the layouts don't have to worry about overflows
+#[derive(Debug, Clone, Copy)]
+struct Rect {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+impl Rect {
+    fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// splits the area into `count` rows of (almost) the same height,
+    /// the last row takes the pixels left over by the division
+    fn rows(&self, count: usize) -> Vec<Rect> {
+        let count = count as i32;
+        (0..count)
+            .map(|i| {
+                let height = self.height / count;
+                let y = self.y + height * i;
+                let height = if i == count - 1 {
+                    self.y + self.height - y
+                } else {
+                    height
+                };
+                Rect::new(self.x, y, self.width, height)
+            })
+            .collect()
+    }
+
+    /// splits the area into rows with heights proportional to `weights`
+    fn weighted_rows(&self, weights: &[f32]) -> Vec<Rect> {
+        let total: f32 = weights.iter().sum();
+        if total <= 0.0 {
+            return self.rows(weights.len());
+        }
+
+        let mut y = self.y;
+        let mut sum = 0.0;
+        weights
+            .iter()
+            .map(|weight| {
+                sum += weight;
+                // placing every edge from the running sum keeps the rounding from adding up
+                let bottom = self.y + (self.height as f32 * sum / total) as i32;
+                let rect = Rect::new(self.x, y, self.width, bottom - y);
+                y = bottom;
+                rect
+            })
+            .collect()
+    }
+
+    /// same as [`Rect::rows`] but for columns
+    fn columns(&self, count: usize) -> Vec<Rect> {
+        self.transposed()
+            .rows(count)
+            .into_iter()
+            .map(|r| r.transposed())
+            .collect()
+    }
+
+    fn transposed(&self) -> Rect {
+        Rect::new(self.y, self.x, self.height, self.width)
+    }
+
+    /// splits off `width` from the left, returns `(left, right)`
+    fn split_left(&self, width: i32) -> (Rect, Rect) {
+        (
+            Rect::new(self.x, self.y, width, self.height),
+            Rect::new(self.x + width, self.y, self.width - width, self.height),
+        )
+    }
+
+    /// splits off `height` from the top, returns `(top, bottom)`
+    fn split_top(&self, height: i32) -> (Rect, Rect) {
+        let (top, bottom) = self.transposed().split_left(height);
+        (top.transposed(), bottom.transposed())
+    }
+
+    /// shrinks the area by the provided amount on each side, grows it when negative
+    fn inset(&self, left: i32, top: i32, right: i32, bottom: i32) -> Rect {
+        Rect::new(
+            self.x + left,
+            self.y + top,
+            self.width - left - right,
+            self.height - top - bottom,
+        )
+    }
+
+    fn to_bounding_box(self) -> BoundingBox {
+        BoundingBox::new(
+            self.x as i16,
+            self.y as i16,
+            self.width.max(1) as u16,
+            self.height.max(1) as u16,
+        )
+    }
+}
+
+/// the space between windows (`inner`) and around the edges of the area (`outer`)
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Gaps {
+    pub inner: u32,
+    pub outer: u32,
+}
+
+/// `weights` has the size weight of every window, the rows of a column are split
+/// proportionally to them in the layouts that stack windows in columns
+pub fn arrange(
+    layout: &TiledLayout,
+    area: &BoundingBox,
+    weights: &[f32],
+    main_factor: f32,
+    main_count: usize,
+    gaps: Gaps,
+) -> Vec<BoundingBox> {
+    let count = weights.len();
+    if count == 0 {
+        return Vec::new();
+    }
+
+    // every window is shrunk by half of the inner gap on each side, so neighbours end up
+    // `inner` apart, and the area is adjusted so the edges end up `outer` away from it
+    let inner_before = gaps.inner as i32 / 2;
+    let inner_after = gaps.inner as i32 - inner_before;
+    let outer = gaps.outer as i32;
+
+    let area = Rect::new(
+        area.x as i32,
+        area.y as i32,
+        area.width as i32,
+        area.height as i32,
+    )
+    .inset(
+        outer - inner_before,
+        outer - inner_before,
+        outer - inner_after,
+        outer - inner_after,
+    );
+
+    let rects = match layout {
+        TiledLayout::MainStack => main_stack(area, weights, main_factor, main_count),
+        TiledLayout::BottomStack => {
+            // the same as main stack, rotated
+            main_stack(area.transposed(), weights, main_factor, main_count)
+                .into_iter()
+                .map(|r| r.transposed())
+                .collect()
+        }
+        TiledLayout::Monocle => vec![area; count],
+        TiledLayout::CenteredMaster => centered_master(area, weights, main_factor, main_count),
+        TiledLayout::ThreeColumn => three_column(area, weights, main_factor, main_count),
+        TiledLayout::Grid => grid(area, count),
+        TiledLayout::Spiral => spiral(area, count, main_factor),
+    };
+
+    rects
+        .into_iter()
+        .map(|r| {
+            r.inset(inner_before, inner_before, inner_after, inner_after)
+                .to_bounding_box()
+        })
+        .collect()
+}
+
+fn main_width(area: &Rect, main_factor: f32) -> i32 {
+    (area.width as f32 * main_factor) as i32
+}
+
+/// main windows stacked in a column on the left, the rest in a column on the right
+fn main_stack(area: Rect, weights: &[f32], main_factor: f32, main_count: usize) -> Vec<Rect> {
+    if main_count == 0 || weights.len() <= main_count {
+        return area.weighted_rows(weights);
+    }
+
+    let (main_weights, stack_weights) = weights.split_at(main_count);
+    let (main, stack) = area.split_left(main_width(&area, main_factor));
+    let mut rects = main.weighted_rows(main_weights);
+    rects.extend(stack.weighted_rows(stack_weights));
+
+    rects
+}
+
+/// main windows in the middle, the rest alternate between the right and left columns
+fn centered_master(area: Rect, weights: &[f32], main_factor: f32, main_count: usize) -> Vec<Rect> {
+    if main_count == 0 || weights.len() <= main_count {
+        return area.weighted_rows(weights);
+    }
+
+    let (main_weights, stack_weights) = weights.split_at(main_count);
+    let stack_count = stack_weights.len();
+    // with a single stack window there's nothing to center around
+    if stack_count == 1 {
+        return main_stack(area, weights, main_factor, main_count);
+    }
+
+    let main_width = main_width(&area, main_factor);
+    let side_width = (area.width - main_width) / 2;
+    let (left, rest) = area.split_left(side_width);
+    let (main, right) = rest.split_left(main_width);
+
+    let right_weights: Vec<f32> = stack_weights.iter().copied().step_by(2).collect();
+    let left_weights: Vec<f32> = stack_weights.iter().copied().skip(1).step_by(2).collect();
+    let mut right_rects = right.weighted_rows(&right_weights).into_iter();
+    let mut left_rects = left.weighted_rows(&left_weights).into_iter();
+
+    let mut rects = main.weighted_rows(main_weights);
+    for i in 0..stack_count {
+        let rect = if i % 2 == 0 {
+            right_rects.next()
+        } else {
+            left_rects.next()
+        };
+        rects.extend(rect);
+    }
+
+    rects
+}
+
+/// main windows on the left, the rest split between a middle and a right column
+fn three_column(area: Rect, weights: &[f32], main_factor: f32, main_count: usize) -> Vec<Rect> {
+    if main_count == 0 || weights.len() <= main_count + 1 {
+        return main_stack(area, weights, main_factor, main_count);
+    }
+
+    let (main_weights, stack_weights) = weights.split_at(main_count);
+    let (main, stack) = area.split_left(main_width(&area, main_factor));
+    let (middle, right) = stack.split_left(stack.width / 2);
+
+    let (middle_weights, right_weights) = stack_weights.split_at(stack_weights.len().div_ceil(2));
+    let mut rects = main.weighted_rows(main_weights);
+    rects.extend(middle.weighted_rows(middle_weights));
+    rects.extend(right.weighted_rows(right_weights));
+
+    rects
+}
+
+/// columns of windows, the rightmost columns get an extra row when the windows don't divide evenly
+fn grid(area: Rect, count: usize) -> Vec<Rect> {
+    let mut columns = (1..=count).find(|c| c * c >= count).unwrap_or(1);
+    // 5 windows look better as 2 columns than 3
+    if count == 5 {
+        columns = 2;
+    }
+
+    let extra = count % columns;
+    area.columns(columns)
+        .into_iter()
+        .enumerate()
+        .flat_map(|(i, column)| {
+            let rows = count / columns + usize::from(i >= columns - extra);
+            column.rows(rows)
+        })
+        .collect()
+}
+
+/// every window takes half of the space left by the previous one, turning clockwise
+fn spiral(area: Rect, count: usize, main_factor: f32) -> Vec<Rect> {
+    let mut rects = Vec::with_capacity(count);
+    let mut rest = area;
+
+    for i in 0..count {
+        if i == count - 1 {
+            rects.push(rest);
+            break;
+        }
+
+        let (window, next) = match i % 4 {
+            0 => {
+                let width = if i == 0 {
+                    main_width(&rest, main_factor)
+                } else {
+                    rest.width / 2
+                };
+                rest.split_left(width)
+            }
+            1 => rest.split_top(rest.height / 2),
+            2 => {
+                let (left, right) = rest.split_left(rest.width / 2);
+                (right, left)
+            }
+            _ => {
+                let (top, bottom) = rest.split_top(rest.height / 2);
+                (bottom, top)
+            }
+        };
+
+        rects.push(window);
+        rest = next;
+    }
+
+    rects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TILED_LAYOUTS: [TiledLayout; 7] = [
+        TiledLayout::MainStack,
+        TiledLayout::BottomStack,
+        TiledLayout::Monocle,
+        TiledLayout::CenteredMaster,
+        TiledLayout::ThreeColumn,
+        TiledLayout::Grid,
+        TiledLayout::Spiral,
+    ];
+
+    const AREA: BoundingBox = BoundingBox {
+        x: 0,
+        y: 0,
+        width: 1000,
+        height: 600,
+    };
+
+    fn arrange_equal(layout: TiledLayout, count: usize, gaps: Gaps) -> Vec<BoundingBox> {
+        arrange(
+            &layout,
+            &AREA,
+            &vec![1.0; count],
+            DEFAULT_MAIN_FACTOR,
+            DEFAULT_MAIN_COUNT,
+            gaps,
+        )
+    }
+
+    fn rects(rects: &[(i16, i16, u16, u16)]) -> Vec<BoundingBox> {
+        rects
+            .iter()
+            .map(|&(x, y, width, height)| BoundingBox::new(x, y, width, height))
+            .collect()
+    }
+
+    #[test]
+    fn single_window_fills_area() {
+        for layout in TILED_LAYOUTS {
+            assert_eq!(
+                arrange_equal(layout, 1, Gaps::default()),
+                vec![AREA],
+                "{layout:?}"
+            );
+        }
+    }
+
+    /// every layout but monocle splits the area without overlaps or holes
+    #[test]
+    fn layouts_cover_area() {
+        for layout in TILED_LAYOUTS {
+            if layout == TiledLayout::Monocle {
+                continue;
+            }
+
+            for count in 1..=12 {
+                let rects = arrange_equal(layout, count, Gaps::default());
+                assert_eq!(rects.len(), count);
+
+                let covered: u32 = rects.iter().map(|r| r.width as u32 * r.height as u32).sum();
+                assert_eq!(covered, 1000 * 600, "{layout:?} with {count} windows");
+                for rect in &rects {
+                    assert!(
+                        rect.x >= 0
+                            && rect.y >= 0
+                            && rect.x as u16 + rect.width <= 1000
+                            && rect.y as u16 + rect.height <= 600,
+                        "{layout:?} with {count} windows placed {rect:?} outside the area"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn main_stack() {
+        assert_eq!(
+            arrange_equal(TiledLayout::MainStack, 3, Gaps::default()),
+            rects(&[(0, 0, 500, 600), (500, 0, 500, 300), (500, 300, 500, 300)])
+        );
+    }
+
+    #[test]
+    fn bottom_stack() {
+        assert_eq!(
+            arrange_equal(TiledLayout::BottomStack, 3, Gaps::default()),
+            rects(&[(0, 0, 1000, 300), (0, 300, 500, 300), (500, 300, 500, 300)])
+        );
+    }
+
+    #[test]
+    fn centered_master_alternates_sides() {
+        assert_eq!(
+            arrange_equal(TiledLayout::CenteredMaster, 3, Gaps::default()),
+            rects(&[(250, 0, 500, 600), (750, 0, 250, 600), (0, 0, 250, 600)])
+        );
+    }
+
+    #[test]
+    fn three_column() {
+        assert_eq!(
+            arrange_equal(TiledLayout::ThreeColumn, 3, Gaps::default()),
+            rects(&[(0, 0, 500, 600), (500, 0, 250, 600), (750, 0, 250, 600)])
+        );
+    }
+
+    #[test]
+    fn monocle_stacks_every_window() {
+        assert_eq!(
+            arrange_equal(TiledLayout::Monocle, 3, Gaps::default()),
+            vec![AREA; 3]
+        );
+    }
+
+    #[test]
+    fn grid() {
+        assert_eq!(
+            arrange_equal(TiledLayout::Grid, 4, Gaps::default()),
+            rects(&[
+                (0, 0, 500, 300),
+                (0, 300, 500, 300),
+                (500, 0, 500, 300),
+                (500, 300, 500, 300)
+            ])
+        );
+
+        // 2 columns, the right one gets the extra window
+        let rects = arrange_equal(TiledLayout::Grid, 5, Gaps::default());
+        assert_eq!(rects.iter().filter(|r| r.x == 0).count(), 2);
+        assert_eq!(rects.iter().filter(|r| r.x == 500).count(), 3);
+    }
+
+    #[test]
+    fn spiral() {
+        assert_eq!(
+            arrange_equal(TiledLayout::Spiral, 3, Gaps::default()),
+            rects(&[(0, 0, 500, 600), (500, 0, 500, 300), (500, 300, 500, 300)])
+        );
+    }
+
+    #[test]
+    fn weights_split_rows() {
+        let rects = arrange(
+            &TiledLayout::MainStack,
+            &AREA,
+            &[1.0, 1.0, 3.0],
+            DEFAULT_MAIN_FACTOR,
+            DEFAULT_MAIN_COUNT,
+            Gaps::default(),
+        );
+
+        assert_eq!(rects[1], BoundingBox::new(500, 0, 500, 150));
+        assert_eq!(rects[2], BoundingBox::new(500, 150, 500, 450));
+    }
+
+    #[test]
+    fn main_factor_and_count() {
+        let arranged = arrange(
+            &TiledLayout::MainStack,
+            &AREA,
+            &[1.0; 3],
+            0.7,
+            2,
+            Gaps::default(),
+        );
+
+        assert_eq!(
+            arranged,
+            rects(&[(0, 0, 700, 300), (0, 300, 700, 300), (700, 0, 300, 600)])
+        );
+    }
+
+    #[test]
+    fn outer_gap_surrounds_single_window() {
+        let gaps = Gaps {
+            inner: 10,
+            outer: 20,
+        };
+
+        assert_eq!(
+            arrange_equal(TiledLayout::MainStack, 1, gaps),
+            rects(&[(20, 20, 960, 560)])
+        );
+    }
+
+    #[test]
+    fn inner_gap_between_windows() {
+        let gaps = Gaps {
+            inner: 10,
+            outer: 0,
+        };
+
+        assert_eq!(
+            arrange_equal(TiledLayout::MainStack, 2, gaps),
+            rects(&[(0, 0, 495, 600), (505, 0, 495, 600)])
+        );
+    }
+
+    #[test]
+    fn windows_never_get_empty() {
+        let gaps = Gaps {
+            inner: 500,
+            outer: 500,
+        };
+
+        for rect in arrange_equal(TiledLayout::Grid, 9, gaps) {
+            assert!(rect.width >= 1 && rect.height >= 1);
+        }
+    }
+}
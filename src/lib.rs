@@ -1,24 +1,37 @@
 use atoms::Atoms;
-use bar::{Bar, BarPosition};
-use layout::ReservedEdges;
+use bar::{Bar, BarPosition, BAR_HEIGHT};
+use layout::{Layout, LayoutManager, ReservedEdges};
+use serde::{Deserialize, Serialize};
+use size_hints::SizeHints;
 use std::marker::PhantomData;
+use std::os::unix::io::RawFd;
+use tags::{TagMask, Tags};
 use windows::WindowError;
 use wm::WM;
 use x11rb::protocol::{randr::ConnectionExt, xproto::ButtonIndex};
 use x11rb::{
     connection::Connection,
-    protocol::xproto::{GetGeometryReply, ModMask, Window},
+    protocol::xproto::{Atom, GetGeometryReply, ModMask, Window},
     CURRENT_TIME,
 };
 
 mod atoms;
 mod bar;
+pub mod config;
 mod connection_wrapper;
+mod font;
+pub mod ipc;
 pub mod layout;
+pub mod rules;
+mod size_hints;
+pub mod tags;
 mod util;
 mod windows;
 pub mod wm;
 
+pub use config::Config;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct RGBA {
     red: u8,
     green: u8,
@@ -29,6 +42,7 @@ pub struct RGBA {
 impl RGBA {
     const CYAN: RGBA = RGBA::new(0x00, 0x55, 0x77, 0xff);
     const BLACK: RGBA = RGBA::new(0x00, 0x00, 0x00, 0xff);
+    const RED: RGBA = RGBA::new(0xff, 0x00, 0x00, 0xff);
     pub const fn new(red: u8, green: u8, blue: u8, alpha: u8) -> Self {
         Self {
             red,
@@ -78,20 +92,100 @@ impl Into<u32> for RGBA {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum WMCommand {
     Execute(String),
+    /// ask the focused window to close, windows that don't support it are disconnected,
+    /// and windows that stopped responding are killed
     CloseWindow,
+    /// kill the process of the focused window
+    KillWindow,
+    /// move a window with the mouse, only works as a mouse binding
     MoveWindow,
-    /// the parameter here is not needed for mouse resizing
+    /// resize a window with the mouse, the parameter is not needed for mouse resizing.
+    /// from the keyboard or IPC it grows the focused floating window by this many pixels
+    /// in both directions at once, shrinks it when negative.
+    /// `ResizeFloating` changes only one side instead
     ResizeWindow(i16),
+    /// move the focused floating window this many pixels in the direction
+    MoveFloating(Direction, u16),
+    /// grow the focused floating window this many pixels to the right or down,
+    /// left and up shrink it
+    ResizeFloating(Direction, u16),
+    /// grow the share of its column the focused tiled window gets, shrinks it when negative
+    ChangeSizeWeight(f32),
+    /// resize the focused window with the arrow keys (or hjkl) until another key is pressed
+    ResizeMode,
     ToggleFullscreen,
     ToggleFloating,
+    /// view only the tag at the provided index
+    ViewTag(usize),
+    /// add or remove the tag at the provided index from the view
+    ToggleViewTag(usize),
+    /// move the focused window to the tag at the provided index
+    MoveToTag(usize),
+    /// add or remove the tag at the provided index from the focused window
+    ToggleTag(usize),
+    FocusNextMonitor,
+    FocusPreviousMonitor,
+    /// move the focused window to the next monitor
+    MoveToNextMonitor,
+    /// move the focused window to the previous monitor
+    MoveToPreviousMonitor,
+    /// reread the config file and apply it without restarting
+    ReloadConfig,
+    /// switch the selected monitor to the next layout
+    NextLayout,
+    /// switch the selected monitor to the previous layout
+    PreviousLayout,
+    SetLayout(Layout),
+    /// grow the main area of the selected monitor by this much, shrinks it when negative
+    ChangeMainFactor(f32),
+    /// add this many main slots to the selected monitor, removes them when negative
+    ChangeMainCount(isize),
+    /// grow the gaps between windows by this many pixels, shrinks them when negative
+    ChangeInnerGap(i32),
+    /// grow the gaps around the screen edges by this many pixels, shrinks them when negative
+    ChangeOuterGap(i32),
+    /// turn the gaps of the selected monitor off or back on
+    ToggleGaps,
+    /// focus the next visible window of the selected monitor
+    FocusNext,
+    /// focus the previous visible window of the selected monitor
+    FocusPrevious,
+    /// focus the closest window in the provided direction
+    FocusDirection(Direction),
+    /// focus the window that was focused before the current one
+    FocusLast,
+    /// swap the focused window with the next tiled window
+    SwapNext,
+    /// swap the focused window with the previous tiled window
+    SwapPrevious,
+    /// swap the focused window with the first tiled window,
+    /// or the first one with the second if it's already focused
+    Zoom,
 }
 
-pub struct Config {
-    pub hotkeys: Vec<Hotkey>,
-    pub mouse_hotkeys: Vec<MouseHotkey>,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Direction {
+    /// the `(x, y)` offset of moving `step` pixels in this direction
+    pub fn offset(self, step: i32) -> (i32, i32) {
+        match self {
+            Direction::Left => (-step, 0),
+            Direction::Right => (step, 0),
+            Direction::Up => (0, -step),
+            Direction::Down => (0, step),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -170,10 +264,51 @@ pub struct WindowProperties {
     is_hidden: bool,
     above: bool,
     below: bool,
+    /// `_NET_WM_STATE_DEMANDS_ATTENTION`, set by the client or by focus stealing prevention
     demands_attention: bool,
+    /// the urgency hint of `WM_HINTS`, kept apart so one source can't clear the other
+    urgency_hint: bool,
 }
 
-impl WindowProperties {}
+impl WindowProperties {
+    /// the flag of a `_NET_WM_STATE_*` atom, `None` if the state isn't supported
+    fn state_mut(&mut self, atoms: &Atoms, state: Atom) -> Option<&mut bool> {
+        let flag = if state == atoms._NET_WM_STATE_MODAL {
+            &mut self.is_modal
+        } else if state == atoms._NET_WM_STATE_STICKY {
+            &mut self.is_sticky
+        } else if state == atoms._NET_WM_STATE_MAXIMIZED_VERT {
+            &mut self.is_maximized_vert
+        } else if state == atoms._NET_WM_STATE_MAXIMIZED_HORZ {
+            &mut self.is_maximized_horz
+        } else if state == atoms._NET_WM_STATE_SHADED {
+            &mut self.is_shaded
+        } else if state == atoms._NET_WM_STATE_SKIP_TASKBAR {
+            &mut self.skip_taskbar
+        } else if state == atoms._NET_WM_STATE_SKIP_PAGER {
+            &mut self.skip_pager
+        } else if state == atoms._NET_WM_STATE_HIDDEN {
+            &mut self.is_hidden
+        } else if state == atoms._NET_WM_STATE_FULLSCREEN {
+            &mut self.is_fullscreen
+        } else if state == atoms._NET_WM_STATE_ABOVE {
+            &mut self.above
+        } else if state == atoms._NET_WM_STATE_BELOW {
+            &mut self.below
+        } else if state == atoms._NET_WM_STATE_DEMANDS_ATTENTION {
+            &mut self.demands_attention
+        } else {
+            return None;
+        };
+
+        Some(flag)
+    }
+
+    /// the window wants attention either through `_NET_WM_STATE` or through `WM_HINTS`
+    pub fn is_urgent(&self) -> bool {
+        self.demands_attention || self.urgency_hint
+    }
+}
 
 #[derive(Default, Debug, Clone)]
 pub struct WindowPropertiesBuilder {
@@ -258,6 +393,7 @@ impl WindowPropertiesBuilder {
             above: self.above.unwrap_or_default(),
             below: self.below.unwrap_or_default(),
             demands_attention: self.demands_attention.unwrap_or_default(),
+            urgency_hint: false,
         }
     }
 }
@@ -269,9 +405,34 @@ pub struct WindowState {
     width: u16,
     height: u16,
     pub window: Window,
+    /// `_NET_WM_NAME` or `WM_NAME`, kept up to date so the bar doesn't have to ask for it
+    title: String,
     r#type: WindowType,
     properties: WindowProperties,
     is_floating: bool,
+    tags: TagMask,
+    /// index of the monitor this window is on
+    monitor: usize,
+    /// the share of its column a tiled window gets compared to the others, 1 by default
+    size_weight: f32,
+    /// the window this one is a dialog (or any other transient window) of, from `WM_TRANSIENT_FOR`
+    transient_for: Option<Window>,
+    size_hints: SizeHints,
+    input_model: InputModel,
+    /// didn't answer `_NET_WM_PING` in time
+    is_hung: bool,
+    /// where the window was before it was maximized
+    unmaximized: Option<SavedGeometry>,
+}
+
+/// the geometry of a window, to put it back later
+#[derive(Debug, Clone)]
+struct SavedGeometry {
+    x: i16,
+    y: i16,
+    width: u16,
+    height: u16,
+    is_floating: bool,
 }
 
 impl WindowState {
@@ -287,10 +448,73 @@ impl WindowState {
             y: geom.y,
             width: geom.width,
             height: geom.height,
+            title: String::new(),
             r#type,
             properties: WindowProperties::default(),
             is_floating,
+            tags: 0,
+            monitor: 0,
+            size_weight: 1.0,
+            transient_for: None,
+            size_hints: SizeHints::default(),
+            input_model: InputModel::default(),
+            is_hung: false,
+            unmaximized: None,
+        }
+    }
+
+    /// splash screens don't get a border
+    pub fn border_width(&self, configured: u32) -> u32 {
+        if self.r#type == WindowType::Splash {
+            0
+        } else {
+            configured
+        }
+    }
+
+    /// floating windows are stacked by layer, fullscreen windows end up on top,
+    /// then menus and splash screens, windows that asked to be above the rest
+    /// and transient windows above their parent. windows that asked to be below go under
+    /// everything else
+    pub fn stack_layer(&self) -> u8 {
+        if self.properties.is_fullscreen {
+            return 5;
         }
+        if self.properties.below {
+            return 0;
+        }
+
+        match self.r#type {
+            WindowType::Menu | WindowType::Splash => 4,
+            _ if self.properties.above => 3,
+            WindowType::Dialog | WindowType::Utility | WindowType::Toolbar => 2,
+            _ if self.transient_for.is_some() => 2,
+            _ => 1,
+        }
+    }
+
+    /// windows that are stacked by the window manager instead of being left where the layout
+    /// put them
+    pub fn is_restacked(&self) -> bool {
+        self.is_floating
+            || self.properties.is_fullscreen
+            || self.properties.above
+            || self.properties.below
+    }
+
+    /// whether the window should be shown when viewing the provided tags,
+    /// minimized windows are never shown
+    pub fn is_visible_on(&self, tagset: TagMask) -> bool {
+        (self.tags & tagset != 0 || self.properties.is_sticky) && !self.properties.is_hidden
+    }
+
+    /// keeps the window at the same place relative to the monitor it moves to,
+    /// clamped so far away monitors don't overflow the position
+    fn keep_relative_position(&mut self, from: &BoundingBox, to: &BoundingBox) {
+        let x = self.x as i32 - from.x as i32 + to.x as i32;
+        let y = self.y as i32 - from.y as i32 + to.y as i32;
+        self.x = x.clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+        self.y = y.clamp(i16::MIN as i32, i16::MAX as i32) as i16;
     }
 
     fn get_property_action(action: u32) -> Result<PropertyAction, XlibError> {
@@ -308,97 +532,6 @@ impl WindowState {
         }
     }
 
-    fn set_window_property(
-        atoms: Atoms,
-        property: u32,
-        action: &PropertyAction,
-        window_props: &mut WindowProperties,
-    ) {
-        match action {
-            PropertyAction::Remove => {
-                if property == atoms._NET_WM_STATE_MODAL {
-                    window_props.is_modal = false;
-                } else if property == atoms._NET_WM_STATE_STICKY {
-                    window_props.is_sticky = false;
-                } else if property == atoms._NET_WM_STATE_MAXIMIZED_VERT {
-                    window_props.is_maximized_vert = false;
-                } else if property == atoms._NET_WM_STATE_MAXIMIZED_HORZ {
-                    window_props.is_maximized_horz = false;
-                } else if property == atoms._NET_WM_STATE_SHADED {
-                    window_props.is_shaded = false;
-                } else if property == atoms._NET_WM_STATE_SKIP_TASKBAR {
-                    window_props.skip_taskbar = false;
-                } else if property == atoms._NET_WM_STATE_SKIP_PAGER {
-                    window_props.skip_pager = false;
-                } else if property == atoms._NET_WM_STATE_HIDDEN {
-                    window_props.is_hidden = false;
-                } else if property == atoms._NET_WM_STATE_FULLSCREEN {
-                    window_props.is_fullscreen = false;
-                } else if property == atoms._NET_WM_STATE_ABOVE {
-                    window_props.above = false;
-                } else if property == atoms._NET_WM_STATE_BELOW {
-                    window_props.below = false;
-                } else if property == atoms._NET_WM_STATE_DEMANDS_ATTENTION {
-                    window_props.demands_attention = false;
-                }
-            }
-            PropertyAction::Add => {
-                if property == atoms._NET_WM_STATE_MODAL {
-                    window_props.is_modal = true;
-                } else if property == atoms._NET_WM_STATE_STICKY {
-                    window_props.is_sticky = true;
-                } else if property == atoms._NET_WM_STATE_MAXIMIZED_VERT {
-                    window_props.is_maximized_vert = true;
-                } else if property == atoms._NET_WM_STATE_MAXIMIZED_HORZ {
-                    window_props.is_maximized_horz = true;
-                } else if property == atoms._NET_WM_STATE_SHADED {
-                    window_props.is_shaded = true;
-                } else if property == atoms._NET_WM_STATE_SKIP_TASKBAR {
-                    window_props.skip_taskbar = true;
-                } else if property == atoms._NET_WM_STATE_SKIP_PAGER {
-                    window_props.skip_pager = true;
-                } else if property == atoms._NET_WM_STATE_HIDDEN {
-                    window_props.is_hidden = true;
-                } else if property == atoms._NET_WM_STATE_FULLSCREEN {
-                    window_props.is_fullscreen = true;
-                } else if property == atoms._NET_WM_STATE_ABOVE {
-                    window_props.above = true;
-                } else if property == atoms._NET_WM_STATE_BELOW {
-                    window_props.below = true;
-                } else if property == atoms._NET_WM_STATE_DEMANDS_ATTENTION {
-                    window_props.demands_attention = true;
-                }
-            }
-            PropertyAction::Toggle => {
-                if property == atoms._NET_WM_STATE_MODAL {
-                    window_props.is_modal = !window_props.is_modal;
-                } else if property == atoms._NET_WM_STATE_STICKY {
-                    window_props.is_sticky = !window_props.is_sticky;
-                } else if property == atoms._NET_WM_STATE_MAXIMIZED_VERT {
-                    window_props.is_maximized_vert = !window_props.is_maximized_vert;
-                } else if property == atoms._NET_WM_STATE_MAXIMIZED_HORZ {
-                    window_props.is_maximized_horz = !window_props.is_maximized_horz;
-                } else if property == atoms._NET_WM_STATE_SHADED {
-                    window_props.is_shaded = !window_props.is_shaded;
-                } else if property == atoms._NET_WM_STATE_SKIP_TASKBAR {
-                    window_props.skip_taskbar = !window_props.skip_taskbar;
-                } else if property == atoms._NET_WM_STATE_SKIP_PAGER {
-                    window_props.skip_pager = !window_props.skip_pager;
-                } else if property == atoms._NET_WM_STATE_HIDDEN {
-                    window_props.is_hidden = !window_props.is_hidden;
-                } else if property == atoms._NET_WM_STATE_FULLSCREEN {
-                    window_props.is_fullscreen = !window_props.is_fullscreen;
-                } else if property == atoms._NET_WM_STATE_ABOVE {
-                    window_props.above = !window_props.above;
-                } else if property == atoms._NET_WM_STATE_BELOW {
-                    window_props.below = !window_props.below;
-                } else if property == atoms._NET_WM_STATE_DEMANDS_ATTENTION {
-                    window_props.demands_attention = !window_props.demands_attention;
-                }
-            }
-        }
-    }
-
     pub fn can_move(&self) -> bool {
         return !self.properties.is_fullscreen
             && !self.properties.is_sticky
@@ -412,12 +545,24 @@ impl WindowState {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
 pub enum PropertyAction {
     Remove,
     Add,
     Toggle,
 }
 
+impl PropertyAction {
+    /// the new value of a state that is currently `value`
+    pub fn apply(&self, value: bool) -> bool {
+        match self {
+            PropertyAction::Remove => false,
+            PropertyAction::Add => true,
+            PropertyAction::Toggle => !value,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum WindowType {
     Desktop,
@@ -430,7 +575,41 @@ pub enum WindowType {
     Utility,
 }
 
-#[derive(Default)]
+/// how a client wants to get keyboard focus, from the input field of `WM_HINTS`
+/// and `WM_TAKE_FOCUS` in `WM_PROTOCOLS`
+///
+/// <https://tronche.com/gui/x/icccm/sec-4.html#s-4.1.7>
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum InputModel {
+    /// the window manager sets the focus, clients without `WM_HINTS` are treated as this
+    #[default]
+    Passive,
+    /// the window manager sets the focus and tells the client with `WM_TAKE_FOCUS`,
+    /// so it can move it to one of its other windows
+    LocallyActive,
+    /// the client sets the focus itself when it gets `WM_TAKE_FOCUS`
+    GloballyActive,
+    /// the window never takes keyboard input
+    NoInput,
+}
+
+impl InputModel {
+    pub fn new(input: bool, take_focus: bool) -> Self {
+        match (input, take_focus) {
+            (true, false) => InputModel::Passive,
+            (true, true) => InputModel::LocallyActive,
+            (false, true) => InputModel::GloballyActive,
+            (false, false) => InputModel::NoInput,
+        }
+    }
+
+    /// whether the client wants `WM_TAKE_FOCUS` messages
+    pub fn takes_focus(&self) -> bool {
+        matches!(self, InputModel::LocallyActive | InputModel::GloballyActive)
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
 pub struct BoundingBox {
     x: i16,
     pub y: i16,
@@ -447,6 +626,13 @@ impl BoundingBox {
             width,
         }
     }
+
+    pub fn contains(&self, x: i16, y: i16) -> bool {
+        x >= self.x
+            && y >= self.y
+            && (x as i32) < self.x as i32 + self.width as i32
+            && (y as i32) < self.y as i32 + self.height as i32
+    }
 }
 
 pub struct Monitor<'a, C: Connection> {
@@ -454,6 +640,10 @@ pub struct Monitor<'a, C: Connection> {
     pub output: String,
     pub bounding_box: BoundingBox,
     pub bar: Bar<'a, C>,
+    pub layout_manager: LayoutManager,
+    pub tags: Tags,
+    /// the window that was focused last on this monitor, like dwm's `sel`
+    pub selected_window: Option<Window>,
     _phantom_data: PhantomData<&'a C>,
 }
 
@@ -467,13 +657,15 @@ impl<'a, C: Connection> Monitor<'a, C> {
                 pos: BarPosition::Top,
                 x: 0,
                 y: 0,
-                status_text: String::new(),
                 height: bar_height,
                 width: bounding_box.width,
                 _phantom_data: PhantomData,
             },
             bounding_box,
             root: Default::default(),
+            layout_manager: LayoutManager::default(),
+            tags: Tags::default(),
+            selected_window: None,
             _phantom_data: PhantomData,
         }
     }
@@ -489,11 +681,13 @@ impl<'a, C: Connection> Monitor<'a, C> {
                 pos: BarPosition::Top,
                 x: 0,
                 y: 0,
-                status_text: String::new(),
                 height: bar_height,
                 width: bar_width,
                 _phantom_data: PhantomData,
             },
+            layout_manager: LayoutManager::default(),
+            tags: Tags::default(),
+            selected_window: None,
             _phantom_data: PhantomData,
         }
     }
@@ -507,39 +701,48 @@ impl<'a, C: Connection> Monitor<'a, C> {
             .reply()?
             .monitors;
 
-        monitors
-            .iter()
-            .map(|m| {
-                let output_info = wm
-                    .conn_wrapper
-                    .connection
-                    .randr_get_output_info(
-                        *m.outputs.first().expect("monitor output"),
-                        CURRENT_TIME,
-                    )?
-                    .reply()?;
+        let mut result = Vec::with_capacity(monitors.len());
+
+        for m in monitors.iter() {
+            // monitors can briefly be left without an output or crtc while (un)plugging a screen
+            let Some(&output) = m.outputs.first() else {
+                continue;
+            };
 
-                let crtc = wm
-                    .conn_wrapper
-                    .connection
-                    .randr_get_crtc_info(output_info.crtc, CURRENT_TIME)?
-                    .reply()?;
+            let output_info = wm
+                .conn_wrapper
+                .connection
+                .randr_get_output_info(output, CURRENT_TIME)?
+                .reply()?;
+
+            if output_info.crtc == x11rb::NONE {
+                continue;
+            }
 
-                let mut monitor = Monitor::with_bbox(
-                    BoundingBox::new(crtc.x, crtc.y, crtc.width, crtc.height),
-                    30,
-                );
+            let crtc = wm
+                .conn_wrapper
+                .connection
+                .randr_get_crtc_info(output_info.crtc, CURRENT_TIME)?
+                .reply()?;
 
-                monitor.root = root.root;
-                monitor.output = String::from_utf8(output_info.name).expect("output name utf8");
+            let mut monitor = Monitor::with_bbox(
+                BoundingBox::new(crtc.x, crtc.y, crtc.width, crtc.height),
+                BAR_HEIGHT,
+            );
 
-                Ok(monitor)
-            })
-            .collect()
+            monitor.root = root.root;
+            monitor.output = String::from_utf8(output_info.name).expect("output name utf8");
+
+            result.push(monitor);
+        }
+
+        Ok(result)
     }
 }
 
-pub fn run<C: Connection>(mut wm: WM<'_, C>) -> Result<(), XlibError> {
+/// runs the event loop, `connection_fd` is the socket of the X connection,
+/// which is waited on together with the IPC socket
+pub fn run<C: Connection>(mut wm: WM<'_, C>, connection_fd: RawFd) -> Result<(), XlibError> {
     // let mut output = std::process::Command::new("kitty").spawn().unwrap();
     // std::thread::spawn(move || {
     //     if let Some(stdout) = output.stdout.take() {
@@ -553,10 +756,26 @@ pub fn run<C: Connection>(mut wm: WM<'_, C>) -> Result<(), XlibError> {
 
     loop {
         wm.refresh();
+        wm.check_pings();
         wm.conn_wrapper.connection.flush()?;
 
-        let event = wm.conn_wrapper.connection.wait_for_event()?;
-        let mut event_option = Some(event);
+        // events that were already read from the socket won't wake up poll
+        let mut event_option = wm.conn_wrapper.connection.poll_for_event()?;
+        if event_option.is_none() {
+            let ipc_fds = wm.ipc_fds();
+            let fds: Vec<RawFd> = std::iter::once(connection_fd).chain(ipc_fds).collect();
+            let readable = util::wait_readable(&fds, wm.ping_timeout())?;
+
+            if readable[1..].iter().any(|&r| r) {
+                wm.handle_ipc()?;
+            }
+            if !readable[0] {
+                continue;
+            }
+
+            event_option = wm.conn_wrapper.connection.poll_for_event()?;
+        }
+
         while let Some(event) = event_option {
             // if let x11rb::protocol::Event::ClientMessage(_) = event {
             //     // This is start_timeout_thread() signaling us to close (most likely).
@@ -597,4 +816,7 @@ pub enum XlibError {
 
     #[error("invalid property action")]
     InvalidAction,
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
 }
@@ -1,8 +1,7 @@
-use bunnuafeth::{run, wm::WM, Config, Hotkey, MouseHotkey, WMCommand, RGBA};
-use x11rb::{
-    connect,
-    protocol::xproto::{ButtonIndex, ModMask},
-};
+use std::os::unix::io::AsRawFd;
+
+use bunnuafeth::{run, wm::WM, Config};
+use x11rb::connect;
 
 fn init_tracing() {
     use tracing_subscriber::prelude::*;
@@ -22,43 +21,22 @@ fn main() {
     let conn1 = std::sync::Arc::new(connection);
     let conn = &*conn1;
 
-    let hotkeys = vec![
-        Hotkey::new(
-            ModMask::M1,
-            x11_keysyms::XK_q,
-            WMCommand::Execute(String::from("kitty")),
-        ),
-        Hotkey::new(
-            ModMask::M1,
-            x11_keysyms::XK_p,
-            WMCommand::Execute(String::from("xprop")),
-        ),
-        Hotkey::new(ModMask::M1, x11_keysyms::XK_c, WMCommand::CloseWindow),
-        Hotkey::new(ModMask::M1, x11_keysyms::XK_f, WMCommand::ToggleFullscreen),
-        Hotkey::new(ModMask::M1, x11_keysyms::XK_v, WMCommand::ToggleFloating),
-    ];
-
-    let mouse_hotkeys = vec![
-        MouseHotkey::new(ModMask::M1, ButtonIndex::M1, WMCommand::MoveWindow),
-        MouseHotkey::new(ModMask::M1, ButtonIndex::M3, WMCommand::ResizeWindow(0)),
-    ];
-
-    let config = Config {
-        hotkeys,
-        mouse_hotkeys,
-    };
+    let config = Config::load();
+    let background = config.colors.background;
 
     let mut wm = WM::new(conn, screen_num, config).expect("create drawable");
-    // 6275a6
-    wm.set_root_background_color(RGBA::new(0x62, 0x75, 0xa6, 0).as_argb_u32())
+    wm.set_root_background_color(background.as_argb_u32())
         .unwrap();
 
-    wm.create_bar().unwrap();
-    wm.bar.update_position(&wm).unwrap();
+    wm.create_bars().unwrap();
+    if let Err(err) = wm.update_status() {
+        tracing::warn!("failed to read the status text: {err}");
+    }
 
     wm.scan_windows().expect("scan windows");
 
     wm.setup().expect("setup window manager");
 
-    run(wm).expect("run window manager");
+    let connection_fd = conn.stream().as_raw_fd();
+    run(wm, connection_fd).expect("run window manager");
 }
@@ -0,0 +1,258 @@
+//! window rules, like dwm's `rules[]` or i3's `for_window`.
+//!
+//! every rule that matches a new window applies its actions in order,
+//! so a later rule overrides the actions of an earlier one.
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::WindowType;
+
+/// the window types of a [`RuleTarget`], rules in the config can't use
+/// `dock` and `desktop` since those windows aren't managed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowKind {
+    Normal,
+    Dialog,
+    Utility,
+    Splash,
+    Menu,
+    Toolbar,
+    Dock,
+    Desktop,
+}
+
+impl From<&WindowType> for WindowKind {
+    fn from(window_type: &WindowType) -> Self {
+        match window_type {
+            WindowType::Normal => WindowKind::Normal,
+            WindowType::Dialog => WindowKind::Dialog,
+            WindowType::Utility => WindowKind::Utility,
+            WindowType::Splash => WindowKind::Splash,
+            WindowType::Menu => WindowKind::Menu,
+            WindowType::Toolbar => WindowKind::Toolbar,
+            WindowType::Dock(_) => WindowKind::Dock,
+            WindowType::Desktop => WindowKind::Desktop,
+        }
+    }
+}
+
+/// the properties of a window rules are matched against
+#[derive(Debug)]
+pub struct RuleTarget {
+    pub class: String,
+    pub instance: String,
+    pub title: String,
+    pub role: String,
+    pub kind: WindowKind,
+}
+
+/// a window matches when every pattern that is set is found in its property,
+/// patterns are regular expressions and can be anchored with `^` and `$`
+#[derive(Debug, Clone, Default)]
+pub struct Rule {
+    pub class: Option<Regex>,
+    pub instance: Option<Regex>,
+    pub title: Option<Regex>,
+    pub role: Option<Regex>,
+    pub kind: Option<WindowKind>,
+    pub actions: RuleActions,
+}
+
+/// what happens to a matched window, `None` leaves the default behaviour
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RuleActions {
+    pub floating: Option<bool>,
+    /// index of the tag the window is placed on
+    pub tag: Option<usize>,
+    /// index of the monitor the window is placed on
+    pub monitor: Option<usize>,
+    pub fullscreen: Option<bool>,
+    pub geometry: Option<RuleGeometry>,
+    /// don't focus the window when it's mapped
+    pub no_focus: Option<bool>,
+    /// show the window on every tag
+    pub sticky: Option<bool>,
+}
+
+/// the initial geometry of a floating window, relative to its monitor.
+/// anything missing keeps the size and position the window asked for
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleGeometry {
+    pub x: Option<i16>,
+    pub y: Option<i16>,
+    pub width: Option<u16>,
+    pub height: Option<u16>,
+}
+
+impl Rule {
+    pub fn matches(&self, target: &RuleTarget) -> bool {
+        let matches = |pattern: &Option<Regex>, value: &str| {
+            pattern.as_ref().is_none_or(|p| p.is_match(value))
+        };
+
+        matches(&self.class, &target.class)
+            && matches(&self.instance, &target.instance)
+            && matches(&self.title, &target.title)
+            && matches(&self.role, &target.role)
+            && self.kind.is_none_or(|kind| kind == target.kind)
+    }
+}
+
+impl RuleActions {
+    /// overrides the actions that are set in `other`
+    fn merge(&mut self, other: &RuleActions) {
+        let RuleActions {
+            floating,
+            tag,
+            monitor,
+            fullscreen,
+            geometry,
+            no_focus,
+            sticky,
+        } = other.clone();
+
+        self.floating = floating.or(self.floating);
+        self.tag = tag.or(self.tag);
+        self.monitor = monitor.or(self.monitor);
+        self.fullscreen = fullscreen.or(self.fullscreen);
+        self.geometry = geometry.or(self.geometry.take());
+        self.no_focus = no_focus.or(self.no_focus);
+        self.sticky = sticky.or(self.sticky);
+    }
+}
+
+/// the combined actions of every rule that matches the window
+pub fn apply_rules(rules: &[Rule], target: &RuleTarget) -> RuleActions {
+    rules.iter().filter(|rule| rule.matches(target)).fold(
+        RuleActions::default(),
+        |mut actions, rule| {
+            actions.merge(&rule.actions);
+            actions
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn firefox() -> RuleTarget {
+        RuleTarget {
+            class: "firefox".to_string(),
+            instance: "Navigator".to_string(),
+            title: "Mozilla Firefox".to_string(),
+            role: "browser".to_string(),
+            kind: WindowKind::Normal,
+        }
+    }
+
+    fn pattern(pattern: &str) -> Option<Regex> {
+        Some(Regex::new(pattern).unwrap())
+    }
+
+    #[test]
+    fn empty_rule_matches_everything() {
+        assert!(Rule::default().matches(&firefox()));
+    }
+
+    #[test]
+    fn every_pattern_has_to_match() {
+        let rule = Rule {
+            class: pattern("^firefox$"),
+            title: pattern("Firefox"),
+            ..Rule::default()
+        };
+        assert!(rule.matches(&firefox()));
+
+        let rule = Rule {
+            class: pattern("^firefox$"),
+            role: pattern("^PictureInPicture$"),
+            ..Rule::default()
+        };
+        assert!(!rule.matches(&firefox()));
+    }
+
+    #[test]
+    fn patterns_are_unanchored() {
+        let rule = Rule {
+            instance: pattern("vig"),
+            ..Rule::default()
+        };
+        assert!(rule.matches(&firefox()));
+
+        let rule = Rule {
+            instance: pattern("^vig"),
+            ..Rule::default()
+        };
+        assert!(!rule.matches(&firefox()));
+    }
+
+    #[test]
+    fn kind_has_to_match() {
+        let rule = Rule {
+            kind: Some(WindowKind::Dialog),
+            ..Rule::default()
+        };
+        assert!(!rule.matches(&firefox()));
+
+        let dialog = RuleTarget {
+            kind: WindowKind::Dialog,
+            ..firefox()
+        };
+        assert!(rule.matches(&dialog));
+    }
+
+    #[test]
+    fn later_rules_override_earlier_ones() {
+        let rules = [
+            Rule {
+                class: pattern("firefox"),
+                actions: RuleActions {
+                    floating: Some(true),
+                    tag: Some(1),
+                    ..RuleActions::default()
+                },
+                ..Rule::default()
+            },
+            Rule {
+                class: pattern("chromium"),
+                actions: RuleActions {
+                    monitor: Some(1),
+                    ..RuleActions::default()
+                },
+                ..Rule::default()
+            },
+            Rule {
+                title: pattern("Firefox"),
+                actions: RuleActions {
+                    tag: Some(3),
+                    sticky: Some(false),
+                    ..RuleActions::default()
+                },
+                ..Rule::default()
+            },
+        ];
+
+        assert_eq!(
+            apply_rules(&rules, &firefox()),
+            RuleActions {
+                floating: Some(true),
+                tag: Some(3),
+                sticky: Some(false),
+                ..RuleActions::default()
+            }
+        );
+    }
+
+    #[test]
+    fn window_kinds() {
+        assert_eq!(WindowKind::from(&WindowType::Menu), WindowKind::Menu);
+        assert_eq!(
+            WindowKind::from(&WindowType::Dock(Default::default())),
+            WindowKind::Dock
+        );
+    }
+}
@@ -0,0 +1,252 @@
+//! `WM_NORMAL_HINTS`, the sizes a client accepts.
+//!
+//! <https://tronche.com/gui/x/icccm/sec-4.html#s-4.1.2.3>
+
+use x11rb::properties::WmSizeHints;
+
+/// the size constraints of a window, every size is without the border.
+/// zero means the hint isn't set
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SizeHints {
+    pub base_width: u16,
+    pub base_height: u16,
+    pub min_width: u16,
+    pub min_height: u16,
+    pub max_width: u16,
+    pub max_height: u16,
+    pub width_inc: u16,
+    pub height_inc: u16,
+    /// the minimum height / width ratio
+    pub min_aspect: f32,
+    /// the maximum width / height ratio
+    pub max_aspect: f32,
+}
+
+impl From<WmSizeHints> for SizeHints {
+    fn from(hints: WmSizeHints) -> Self {
+        let size = |size: Option<(i32, i32)>| {
+            size.map(|(w, h)| {
+                (
+                    w.clamp(0, u16::MAX as i32) as u16,
+                    h.clamp(0, u16::MAX as i32) as u16,
+                )
+            })
+        };
+
+        // ICCCM says the base size falls back to the min size and the other way around
+        let base = size(hints.base_size)
+            .or(size(hints.min_size))
+            .unwrap_or_default();
+        let min = size(hints.min_size)
+            .or(size(hints.base_size))
+            .unwrap_or_default();
+        let max = size(hints.max_size).unwrap_or_default();
+        let inc = size(hints.size_increment).unwrap_or_default();
+
+        let (min_aspect, max_aspect) = hints
+            .aspect
+            .filter(|(min, max)| {
+                min.numerator > 0 && min.denominator > 0 && max.numerator > 0 && max.denominator > 0
+            })
+            .map(|(min, max)| {
+                (
+                    min.denominator as f32 / min.numerator as f32,
+                    max.numerator as f32 / max.denominator as f32,
+                )
+            })
+            .unwrap_or_default();
+
+        Self {
+            base_width: base.0,
+            base_height: base.1,
+            min_width: min.0,
+            min_height: min.1,
+            max_width: max.0,
+            max_height: max.1,
+            width_inc: inc.0,
+            height_inc: inc.1,
+            min_aspect,
+            max_aspect,
+        }
+    }
+}
+
+impl SizeHints {
+    /// windows that can't be resized should float
+    pub fn is_fixed(&self) -> bool {
+        self.max_width > 0
+            && self.max_height > 0
+            && self.max_width == self.min_width
+            && self.max_height == self.min_height
+    }
+
+    /// the closest size to the provided one that the window accepts, like dwm's `applysizehints`
+    pub fn apply(&self, width: u16, height: u16) -> (u16, u16) {
+        let (mut width, mut height) = (width as i32, height as i32);
+        let (base_width, base_height) = (self.base_width as i32, self.base_height as i32);
+        let base_is_min = self.base_width == self.min_width && self.base_height == self.min_height;
+
+        // the aspect ratio doesn't include the base size, unless it's the min size
+        if !base_is_min {
+            width -= base_width;
+            height -= base_height;
+        }
+
+        if self.min_aspect > 0.0 && self.max_aspect > 0.0 && width > 0 && height > 0 {
+            if self.max_aspect < width as f32 / height as f32 {
+                width = (height as f32 * self.max_aspect + 0.5) as i32;
+            } else if self.min_aspect < height as f32 / width as f32 {
+                height = (width as f32 * self.min_aspect + 0.5) as i32;
+            }
+        }
+
+        // the increments always start from the base size
+        if base_is_min {
+            width -= base_width;
+            height -= base_height;
+        }
+
+        if self.width_inc > 0 {
+            width -= width.rem_euclid(self.width_inc as i32);
+        }
+        if self.height_inc > 0 {
+            height -= height.rem_euclid(self.height_inc as i32);
+        }
+
+        width = (width + base_width).max(self.min_width as i32);
+        height = (height + base_height).max(self.min_height as i32);
+
+        if self.max_width > 0 {
+            width = width.min(self.max_width as i32);
+        }
+        if self.max_height > 0 {
+            height = height.min(self.max_height as i32);
+        }
+
+        (
+            width.clamp(1, u16::MAX as i32) as u16,
+            height.clamp(1, u16::MAX as i32) as u16,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use x11rb::properties::AspectRatio;
+
+    use super::*;
+
+    #[test]
+    fn no_hints_keeps_size() {
+        assert_eq!(SizeHints::default().apply(123, 45), (123, 45));
+        assert_eq!(SizeHints::default().apply(0, 0), (1, 1));
+    }
+
+    #[test]
+    fn min_and_max_size() {
+        let hints = SizeHints {
+            base_width: 200,
+            base_height: 100,
+            min_width: 200,
+            min_height: 100,
+            max_width: 400,
+            max_height: 300,
+            ..SizeHints::default()
+        };
+
+        assert_eq!(hints.apply(100, 500), (200, 300));
+        assert_eq!(hints.apply(300, 200), (300, 200));
+    }
+
+    #[test]
+    fn increments_start_from_base_size() {
+        // like a terminal with 6x13 cells and a 2px padding
+        let hints = SizeHints {
+            base_width: 2,
+            base_height: 2,
+            min_width: 10,
+            min_height: 20,
+            width_inc: 6,
+            height_inc: 13,
+            ..SizeHints::default()
+        };
+
+        assert_eq!(hints.apply(100, 100), (98, 93));
+        assert_eq!(hints.apply(5, 5), (10, 20));
+    }
+
+    #[test]
+    fn aspect_ratio() {
+        let hints = SizeHints {
+            min_aspect: 9.0 / 16.0,
+            max_aspect: 16.0 / 9.0,
+            ..SizeHints::default()
+        };
+
+        assert_eq!(hints.apply(1000, 1000), (1000, 563));
+        assert_eq!(hints.apply(1920, 500), (889, 500));
+        assert_eq!(hints.apply(1600, 900), (1600, 900));
+    }
+
+    #[test]
+    fn fixed_size() {
+        let fixed = SizeHints {
+            min_width: 300,
+            min_height: 200,
+            max_width: 300,
+            max_height: 200,
+            ..SizeHints::default()
+        };
+        assert!(fixed.is_fixed());
+
+        let unbounded = SizeHints {
+            max_width: 0,
+            max_height: 0,
+            ..fixed
+        };
+        assert!(!unbounded.is_fixed());
+        assert!(!SizeHints::default().is_fixed());
+    }
+
+    #[test]
+    fn base_and_min_size_fall_back_to_each_other() {
+        let hints = SizeHints::from(WmSizeHints {
+            min_size: Some((100, 50)),
+            ..WmSizeHints::new()
+        });
+        assert_eq!((hints.base_width, hints.base_height), (100, 50));
+
+        let hints = SizeHints::from(WmSizeHints {
+            base_size: Some((30, 40)),
+            ..WmSizeHints::new()
+        });
+        assert_eq!((hints.min_width, hints.min_height), (30, 40));
+    }
+
+    #[test]
+    fn sizes_are_clamped() {
+        let hints = SizeHints::from(WmSizeHints {
+            max_size: Some((-5, 100_000)),
+            ..WmSizeHints::new()
+        });
+
+        assert_eq!((hints.max_width, hints.max_height), (0, u16::MAX));
+    }
+
+    #[test]
+    fn aspect_ratio_from_hints() {
+        let hints = SizeHints::from(WmSizeHints {
+            aspect: Some((AspectRatio::new(4, 3), AspectRatio::new(16, 9))),
+            ..WmSizeHints::new()
+        });
+        assert_eq!(hints.min_aspect, 3.0 / 4.0);
+        assert_eq!(hints.max_aspect, 16.0 / 9.0);
+
+        // a zero would divide by zero
+        let hints = SizeHints::from(WmSizeHints {
+            aspect: Some((AspectRatio::new(0, 3), AspectRatio::new(16, 9))),
+            ..WmSizeHints::new()
+        });
+        assert_eq!((hints.min_aspect, hints.max_aspect), (0.0, 0.0));
+    }
+}
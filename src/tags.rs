@@ -0,0 +1,201 @@
+/// a bitmask of tags, where bit `n` is the tag at index `n`
+pub type TagMask = u32;
+
+pub const DEFAULT_TAGS: [&str; 9] = ["1", "2", "3", "4", "5", "6", "7", "8", "9"];
+
+/// the value of `_NET_WM_DESKTOP` for windows that are shown on all desktops
+pub const ALL_DESKTOPS: u32 = 0xFFFFFFFF;
+
+#[derive(Debug, Clone)]
+pub struct Tags {
+    names: Vec<String>,
+    selected: TagMask,
+    previous: TagMask,
+}
+
+impl Tags {
+    pub fn new(names: Vec<String>) -> Self {
+        // a mask is 32 bits wide, so any tags after that can't be represented
+        let names = names.into_iter().take(TagMask::BITS as usize).collect();
+
+        Self {
+            names,
+            selected: 1,
+            previous: 1,
+        }
+    }
+
+    /// renames the tags, the view is kept as long as the viewed tags still exist
+    pub fn set_names(&mut self, names: Vec<String>) {
+        let selected = self.selected;
+        let previous = self.previous;
+        *self = Tags::new(names);
+
+        if selected & self.all() != 0 {
+            self.selected = selected & self.all();
+        }
+        if previous & self.all() != 0 {
+            self.previous = previous & self.all();
+        }
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// mask with every existing tag set
+    pub fn all(&self) -> TagMask {
+        if self.names.len() >= TagMask::BITS as usize {
+            TagMask::MAX
+        } else {
+            (1 << self.names.len()) - 1
+        }
+    }
+
+    /// returns the mask of the tag at `index`, or `None` if it doesn't exist
+    pub fn mask(&self, index: usize) -> Option<TagMask> {
+        if index < self.names.len() {
+            Some(1 << index)
+        } else {
+            None
+        }
+    }
+
+    /// the currently viewed tags
+    pub fn selected(&self) -> TagMask {
+        self.selected
+    }
+
+    /// views only the provided tags, returns false if nothing changed
+    pub fn view(&mut self, mask: TagMask) -> bool {
+        let mask = mask & self.all();
+        if mask == 0 || mask == self.selected {
+            return false;
+        }
+
+        self.previous = self.selected;
+        self.selected = mask;
+
+        true
+    }
+
+    /// adds or removes the provided tags from the view,
+    /// returns false if nothing changed (the view can't be left empty)
+    pub fn toggle_view(&mut self, mask: TagMask) -> bool {
+        let new_selected = (self.selected ^ mask) & self.all();
+        if new_selected == 0 {
+            return false;
+        }
+
+        self.previous = self.selected;
+        self.selected = new_selected;
+
+        true
+    }
+
+    /// the index used for `_NET_CURRENT_DESKTOP`, which is the lowest selected tag
+    pub fn current_desktop(&self) -> u32 {
+        first_tag(self.selected).unwrap_or_default()
+    }
+}
+
+impl Default for Tags {
+    fn default() -> Self {
+        Self::new(DEFAULT_TAGS.iter().map(|t| t.to_string()).collect())
+    }
+}
+
+/// index of the lowest tag in the mask
+pub fn first_tag(mask: TagMask) -> Option<u32> {
+    if mask == 0 {
+        None
+    } else {
+        Some(mask.trailing_zeros())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(count: usize) -> Tags {
+        Tags::new((1..=count).map(|n| n.to_string()).collect())
+    }
+
+    #[test]
+    fn first_tag_is_viewed() {
+        let tags = Tags::default();
+
+        assert_eq!(tags.len(), 9);
+        assert_eq!(tags.selected(), 1);
+        assert_eq!(tags.current_desktop(), 0);
+    }
+
+    #[test]
+    fn all_covers_every_tag() {
+        assert_eq!(tags(1).all(), 0b1);
+        assert_eq!(tags(9).all(), 0x1ff);
+        assert_eq!(tags(32).all(), TagMask::MAX);
+    }
+
+    #[test]
+    fn tags_past_the_mask_are_dropped() {
+        let tags = tags(40);
+
+        assert_eq!(tags.len(), 32);
+        assert_eq!(tags.mask(31), Some(1 << 31));
+        assert_eq!(tags.mask(32), None);
+    }
+
+    #[test]
+    fn view_ignores_missing_and_current_tags() {
+        let mut tags = tags(3);
+
+        assert!(!tags.view(1));
+        assert!(!tags.view(0));
+        assert!(!tags.view(1 << 5));
+        assert!(tags.view(0b110 | 1 << 5));
+        assert_eq!(tags.selected(), 0b110);
+        assert_eq!(tags.current_desktop(), 1);
+    }
+
+    #[test]
+    fn toggle_view_never_empties_the_view() {
+        let mut tags = tags(3);
+
+        assert!(tags.toggle_view(0b100));
+        assert_eq!(tags.selected(), 0b101);
+        assert!(tags.toggle_view(0b001));
+        assert_eq!(tags.selected(), 0b100);
+        assert!(!tags.toggle_view(0b100));
+        assert_eq!(tags.selected(), 0b100);
+    }
+
+    #[test]
+    fn renaming_keeps_the_view_of_existing_tags() {
+        let mut tags = tags(9);
+        tags.view(0b1_0000_0100);
+        tags.set_names(vec!["a".to_string(), "b".to_string(), "c".to_string()]);
+        assert_eq!(tags.selected(), 0b100);
+
+        tags.view(0b100);
+        tags.set_names(vec!["a".to_string()]);
+        assert_eq!(tags.selected(), 1);
+        assert_eq!(tags.names(), ["a"]);
+    }
+
+    #[test]
+    fn first_tag_of_mask() {
+        assert_eq!(first_tag(0), None);
+        assert_eq!(first_tag(0b1000), Some(3));
+        assert_eq!(first_tag(1 << 31 | 1 << 4), Some(4));
+    }
+}
@@ -1,5 +1,7 @@
 use std::ffi::OsStr;
+use std::os::unix::io::RawFd;
 use std::process::Command;
+use std::time::Duration;
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 
@@ -64,3 +66,38 @@ pub enum ProcessError {
     #[error("Process exited with error code {0}")]
     ErrorCode(i32),
 }
+
+/// blocks until at least one of the file descriptors is readable (or closed),
+/// or the timeout passes, and returns which ones are
+pub fn wait_readable(fds: &[RawFd], timeout: Option<Duration>) -> std::io::Result<Vec<bool>> {
+    let mut poll_fds: Vec<libc::pollfd> = fds
+        .iter()
+        .map(|&fd| libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        })
+        .collect();
+    let timeout = timeout.map_or(-1, |t| t.as_millis().min(i32::MAX as u128) as i32);
+
+    loop {
+        // SAFETY: the pointer and length come from a live Vec
+        let res = unsafe {
+            libc::poll(
+                poll_fds.as_mut_ptr(),
+                poll_fds.len() as libc::nfds_t,
+                timeout,
+            )
+        };
+        if res >= 0 {
+            break;
+        }
+
+        let err = std::io::Error::last_os_error();
+        if err.kind() != std::io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+
+    Ok(poll_fds.iter().map(|p| p.revents != 0).collect())
+}
@@ -1,12 +1,16 @@
 use indexmap::IndexMap;
 
-use crate::WindowState;
+use crate::{tags::TagMask, WindowState};
 
 pub type WindowHandle = u32;
 
 #[derive(Debug, Default)]
 pub struct Windows {
     windows: IndexMap<WindowHandle, WindowState>,
+    /// the managed windows in the order they were mapped, oldest first
+    mapping_order: Vec<WindowHandle>,
+    /// the managed windows in the order they are stacked, topmost first
+    stacking_order: Vec<WindowHandle>,
     unmanaged_windows: Vec<WindowState>,
     focus: Option<WindowHandle>,
     previous_focus: Option<WindowHandle>,
@@ -32,24 +36,31 @@ impl Windows {
         self.windows.iter_mut().find(predicate)
     }
     pub fn add_window(&mut self, window_handle: WindowHandle, win_state: WindowState) {
-        self.windows.insert(window_handle, win_state);
+        if self.windows.insert(window_handle, win_state).is_none() {
+            self.mapping_order.push(window_handle);
+            self.stacking_order.insert(0, window_handle);
+        }
     }
     pub fn add_unmanaged_window(&mut self, win_state: WindowState) {
         self.unmanaged_windows.push(win_state);
     }
-    /// removes a window and shifts all windows that follow it
-    /// and moves focus to the next index
+    /// removes a window and shifts all windows that follow it,
+    /// nothing is focused afterwards if the removed window was focused
     pub fn remove_window(
         &mut self,
         window_handle: WindowHandle,
     ) -> Option<(WindowHandle, WindowState)> {
-        if let Some(window) = self.windows.shift_remove_entry(&window_handle) {
-            self.previous_focus = self.focus;
-            self.focus = self.next_window(window_handle).map(|(wh, _)| wh).copied();
-            return Some(window);
+        let window = self.windows.shift_remove_entry(&window_handle)?;
+        self.mapping_order.retain(|&w| w != window_handle);
+        self.stacking_order.retain(|&w| w != window_handle);
+        if self.focus == Some(window_handle) {
+            self.focus = None;
+        }
+        if self.previous_focus == Some(window_handle) {
+            self.previous_focus = None;
         }
 
-        None
+        Some(window)
     }
     /// removes an unmanaged window and shifts all windows that follow it
     pub fn remove_unmanaged_window(&mut self, window_handle: WindowHandle) -> Option<WindowState> {
@@ -72,7 +83,7 @@ impl Windows {
             return None;
         };
 
-        let next_window_index = (window_index + 1) % (self.windows.len() - 1);
+        let next_window_index = (window_index + 1) % self.windows.len();
 
         self.windows.get_index(next_window_index)
     }
@@ -85,19 +96,26 @@ impl Windows {
             .get(&window_handle)
             .ok_or(WindowError::InvalidWindowFocus)?;
 
-        self.previous_focus = self.focus;
+        let old_focus = self.focus;
+        // focusing the same window again shouldn't forget the previous one,
+        // and neither should focusing after nothing was focused, like when the pointer left it
+        if old_focus.is_some_and(|old_focus| old_focus != window_handle) {
+            self.previous_focus = old_focus;
+        }
         self.focus = Some(window_handle);
 
-        if let Some(previous_focus) = self.previous_focus {
-            return Ok(self.windows.get(&previous_focus));
+        if let Some(old_focus) = old_focus {
+            return Ok(self.windows.get(&old_focus));
         }
 
         Ok(None)
     }
     /// removes current focus
     pub fn unfocus(&mut self) {
-        self.previous_focus = self.focus;
-        self.focus = None;
+        if self.focus.is_some() {
+            self.previous_focus = self.focus;
+            self.focus = None;
+        }
     }
     /// swaps the order of 2 windows.
     /// does nothing if one or more doesn't exist
@@ -110,13 +128,15 @@ impl Windows {
         };
         self.windows.swap_indices(first, second);
     }
-    /// moves the provided window to the top of the stack
-    /// does nothing if provided window doesn't exist in the stack
-    pub fn move_to_top(&mut self, window_handle: WindowHandle) {
-        let Some(window_index) = self.windows.get_index_of(&window_handle) else {
+    /// moves the provided window to the top of the stacking order, the window list keeps its order.
+    /// does nothing if provided window doesn't exist
+    pub fn raise(&mut self, window_handle: WindowHandle) {
+        let Some(window_index) = self.stacking_order.iter().position(|&w| w == window_handle)
+        else {
             return;
         };
-        self.windows.move_index(window_index, 0);
+        self.stacking_order.remove(window_index);
+        self.stacking_order.insert(0, window_handle);
     }
     pub fn focused(&self) -> Option<&WindowState> {
         let Some(focus) = self.focus else {
@@ -139,9 +159,27 @@ impl Windows {
 
         self.windows.get(&previos_focus)
     }
+    /// the managed windows in the order they were mapped, oldest first
+    pub fn mapping_order(&self) -> &[WindowHandle] {
+        &self.mapping_order
+    }
     pub fn windows(&self) -> Vec<&WindowState> {
         self.windows.values().collect()
     }
+    /// the managed windows in the order they are stacked, topmost first
+    pub fn stacked_windows(&self) -> Vec<&WindowState> {
+        self.stacking_order
+            .iter()
+            .filter_map(|w| self.windows.get(w))
+            .collect()
+    }
+    /// managed windows of the provided monitor that are shown on the provided tags
+    pub fn visible_windows(&self, monitor: usize, tagset: TagMask) -> Vec<&WindowState> {
+        self.windows
+            .values()
+            .filter(|w| w.monitor == monitor && w.is_visible_on(tagset))
+            .collect()
+    }
     pub fn windows_mut(&mut self) -> Vec<&mut WindowState> {
         self.windows.values_mut().collect()
     }
@@ -170,3 +208,81 @@ pub enum WindowError {
     #[error("provided window can't be focused")]
     InvalidWindowFocus,
 }
+
+#[cfg(test)]
+mod tests {
+    use x11rb::protocol::xproto::GetGeometryReply;
+
+    use super::*;
+    use crate::WindowType;
+
+    fn windows(handles: &[WindowHandle]) -> Windows {
+        let mut windows = Windows::new();
+        for &handle in handles {
+            let win_state = WindowState::new(
+                handle,
+                &GetGeometryReply::default(),
+                WindowType::Normal,
+                false,
+            );
+            windows.add_window(handle, win_state);
+        }
+
+        windows
+    }
+
+    fn previous(windows: &Windows) -> Option<WindowHandle> {
+        windows.previos_focus().map(|w| w.window)
+    }
+
+    #[test]
+    fn focus_remembers_previous_window() {
+        let mut windows = windows(&[1, 2]);
+        windows.focus_window(1).unwrap();
+        windows.focus_window(2).unwrap();
+
+        assert_eq!(previous(&windows), Some(1));
+    }
+
+    #[test]
+    fn focus_after_unfocus_keeps_previous_window() {
+        let mut windows = windows(&[1, 2]);
+        windows.focus_window(1).unwrap();
+        windows.unfocus();
+        windows.focus_window(2).unwrap();
+
+        assert_eq!(windows.focused().map(|w| w.window), Some(2));
+        assert_eq!(previous(&windows), Some(1));
+    }
+
+    #[test]
+    fn focus_same_window_keeps_previous_window() {
+        let mut windows = windows(&[1, 2]);
+        windows.focus_window(1).unwrap();
+        windows.focus_window(2).unwrap();
+        windows.focus_window(2).unwrap();
+
+        assert_eq!(previous(&windows), Some(1));
+    }
+
+    #[test]
+    fn removed_window_is_not_previous() {
+        let mut windows = windows(&[1, 2]);
+        windows.focus_window(1).unwrap();
+        windows.focus_window(2).unwrap();
+        windows.remove_window(1);
+
+        assert_eq!(previous(&windows), None);
+    }
+
+    #[test]
+    fn raising_keeps_window_order() {
+        let mut windows = windows(&[1, 2, 3]);
+        windows.raise(2);
+
+        let order: Vec<_> = windows.windows().iter().map(|w| w.window).collect();
+        let stacking: Vec<_> = windows.stacked_windows().iter().map(|w| w.window).collect();
+        assert_eq!(order, [1, 2, 3]);
+        assert_eq!(stacking, [2, 3, 1]);
+    }
+}
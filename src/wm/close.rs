@@ -0,0 +1,185 @@
+use std::time::{Duration, Instant};
+
+use x11rb::{
+    connection::Connection,
+    protocol::xproto::{AtomEnum, ClientMessageEvent, ConnectionExt, EventMask, Window},
+    CURRENT_TIME,
+};
+
+use crate::{ipc::Event, XlibError};
+
+use super::WM;
+
+/// how long a client gets to answer `_NET_WM_PING` before it's flagged as hung
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+impl<'a, C: Connection> WM<'a, C> {
+    /// asks the window to close with `WM_DELETE_WINDOW`, clients that don't support it are
+    /// disconnected with `XKillClient`, and hung clients are killed
+    pub fn close_window(&mut self, window: Window) -> Result<(), XlibError> {
+        if self.windows.get_window(window).is_some_and(|w| w.is_hung) {
+            tracing::info!("window {window} doesn't respond, killing it");
+            return self.kill_window(window);
+        }
+
+        let protocols = self.wm_protocols(window)?;
+        let atoms = self.conn_wrapper.atoms;
+        if !protocols.contains(&atoms.WM_DELETE_WINDOW) {
+            tracing::debug!("window {window} doesn't support WM_DELETE_WINDOW, killing its client");
+            self.conn_wrapper.connection.kill_client(window)?;
+            return Ok(());
+        }
+
+        self.send_protocol(window, atoms.WM_DELETE_WINDOW)?;
+
+        // a client that doesn't answer the ping is hung, and won't close either
+        if protocols.contains(&atoms._NET_WM_PING) && !self.pings.contains_key(&window) {
+            self.send_ping(window)?;
+        }
+
+        Ok(())
+    }
+
+    /// kills the process of the window from `_NET_WM_PID`,
+    /// falling back to `XKillClient` when the process isn't running on this machine
+    pub fn kill_window(&mut self, window: Window) -> Result<(), XlibError> {
+        match self.local_pid(window)? {
+            Some(pid) => {
+                tracing::debug!("killing process {pid} of window {window}");
+                // SAFETY: kill only sends a signal
+                if unsafe { libc::kill(pid, libc::SIGKILL) } != 0 {
+                    let err = std::io::Error::last_os_error();
+                    tracing::warn!("couldn't kill process {pid}: {err}, killing its client");
+                    self.conn_wrapper.connection.kill_client(window)?;
+                }
+            }
+            None => {
+                self.conn_wrapper.connection.kill_client(window)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// `_NET_WM_PID`, only if `WM_CLIENT_MACHINE` says the process runs on this machine,
+    /// otherwise the pid belongs to some other process
+    fn local_pid(&self, window: Window) -> Result<Option<libc::pid_t>, XlibError> {
+        let machine = self
+            .conn_wrapper
+            .connection
+            .get_property(
+                false,
+                window,
+                AtomEnum::WM_CLIENT_MACHINE,
+                AtomEnum::STRING,
+                0,
+                u32::MAX,
+            )?
+            .reply()?;
+        if hostname().is_none_or(|hostname| machine.value != hostname) {
+            return Ok(None);
+        }
+
+        let reply = self
+            .conn_wrapper
+            .connection
+            .get_property(
+                false,
+                window,
+                self.conn_wrapper.atoms._NET_WM_PID,
+                AtomEnum::CARDINAL,
+                0,
+                1,
+            )?
+            .reply()?;
+
+        let pid = reply
+            .value32()
+            .and_then(|mut values| values.next())
+            .and_then(|pid| libc::pid_t::try_from(pid).ok())
+            // never signal process groups or ourselves
+            .filter(|&pid| pid > 0 && pid as u32 != std::process::id());
+
+        Ok(pid)
+    }
+
+    /// <https://specifications.freedesktop.org/wm-spec/1.3/ar01s06.html#idm45381391268672>
+    fn send_ping(&mut self, window: Window) -> Result<(), XlibError> {
+        let event = ClientMessageEvent::new(
+            32,
+            window,
+            self.conn_wrapper.atoms.WM_PROTOCOLS,
+            [
+                self.conn_wrapper.atoms._NET_WM_PING,
+                CURRENT_TIME,
+                window,
+                0,
+                0,
+            ],
+        );
+        self.conn_wrapper
+            .connection
+            .send_event(false, window, EventMask::NO_EVENT, event)?;
+        self.pings.insert(window, Instant::now());
+
+        Ok(())
+    }
+
+    /// the client answered a ping by sending it back to the root window
+    pub(crate) fn handle_pong(&mut self, window: Window) {
+        self.pings.remove(&window);
+
+        if let Some((_, win_state)) = self.windows.get_window_mut_by(|(_, w)| w.window == window) {
+            if win_state.is_hung {
+                tracing::info!("window {window} responds again");
+                win_state.is_hung = false;
+                self.emit(Event::Hung {
+                    window,
+                    hung: false,
+                });
+            }
+        }
+    }
+
+    /// flags the windows that didn't answer their ping in time
+    pub fn check_pings(&mut self) {
+        let expired: Vec<Window> = self
+            .pings
+            .iter()
+            .filter(|(_, sent)| sent.elapsed() >= PING_TIMEOUT)
+            .map(|(&window, _)| window)
+            .collect();
+
+        for window in expired {
+            self.pings.remove(&window);
+
+            if let Some((_, win_state)) =
+                self.windows.get_window_mut_by(|(_, w)| w.window == window)
+            {
+                tracing::warn!("window {window} didn't answer the ping, closing it again kills it");
+                win_state.is_hung = true;
+                self.emit(Event::Hung { window, hung: true });
+            }
+        }
+    }
+
+    /// how long until the oldest ping times out, `None` if there are no pings
+    pub fn ping_timeout(&self) -> Option<Duration> {
+        self.pings
+            .values()
+            .map(|sent| PING_TIMEOUT.saturating_sub(sent.elapsed()))
+            .min()
+    }
+}
+
+/// the name of this machine, as clients put it in `WM_CLIENT_MACHINE`
+fn hostname() -> Option<Vec<u8>> {
+    let mut buffer = [0u8; 256];
+    // SAFETY: the pointer and length come from a live buffer
+    if unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) } != 0 {
+        return None;
+    }
+
+    let len = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+    Some(buffer[..len].to_vec())
+}
@@ -0,0 +1,149 @@
+use std::process::Command;
+
+use x11rb::connection::Connection;
+
+use crate::{util::CommandExt, WMCommand, XlibError};
+
+use super::WM;
+
+impl<'a, C: Connection> WM<'a, C> {
+    /// runs a command that doesn't depend on the event that triggered it,
+    /// used by key bindings, mouse bindings and IPC.
+    ///
+    /// `MoveWindow` needs the pointer position, so the button handler deals with it instead.
+    /// `ResizeWindow` bound to a mouse button is handled there too,
+    /// anywhere else it resizes the focused floating window by its step
+    pub fn run_command(&mut self, command: &WMCommand) -> Result<(), XlibError> {
+        match command {
+            WMCommand::Execute(command) => {
+                // TODO: does this work like bash?
+                let mut command = command.split(' ');
+                if let Some(program) = command.next() {
+                    if let Err(e) = Command::new(program)
+                        .with_args(command.collect::<Vec<&str>>())
+                        .spawn()
+                    {
+                        tracing::error!("command failed: {e}");
+                    }
+                }
+            }
+            WMCommand::CloseWindow => {
+                if let Some(window) = self.windows.focused().map(|fw| fw.window) {
+                    if window == self.screen().root {
+                        return Ok(());
+                    }
+
+                    self.close_window(window)?;
+                }
+            }
+            WMCommand::KillWindow => {
+                if let Some(window) = self.windows.focused().map(|fw| fw.window) {
+                    if window == self.screen().root {
+                        return Ok(());
+                    }
+
+                    self.kill_window(window)?;
+                }
+            }
+            WMCommand::MoveWindow => {
+                tracing::debug!("{command:?} can only be used from a mouse binding");
+            }
+            WMCommand::ResizeWindow(step) => {
+                self.resize_floating(i32::from(*step), i32::from(*step))?;
+            }
+            WMCommand::MoveFloating(direction, step) => {
+                let (dx, dy) = direction.offset(i32::from(*step));
+                self.move_floating(dx, dy)?;
+            }
+            WMCommand::ResizeFloating(direction, step) => {
+                let (dw, dh) = direction.offset(i32::from(*step));
+                self.resize_floating(dw, dh)?;
+            }
+            WMCommand::ChangeSizeWeight(delta) => self.change_size_weight(*delta)?,
+            WMCommand::ResizeMode => self.enter_resize_mode()?,
+            WMCommand::ToggleFullscreen => {
+                if let Some(fw_state) = self.windows.focused() {
+                    if fw_state.properties.is_fullscreen {
+                        self.unfullscreen_window(fw_state.window)?;
+                    } else {
+                        self.fullscreen_window(fw_state.window)?;
+                    }
+                }
+            }
+            WMCommand::ToggleFloating => {
+                if let Some(fw_state) = self.windows.focused_mut() {
+                    fw_state.is_floating = !fw_state.is_floating;
+                    self.arrange()?;
+                }
+            }
+            WMCommand::ViewTag(index) => self.view_tag(*index)?,
+            WMCommand::ToggleViewTag(index) => self.toggle_view_tag(*index)?,
+            WMCommand::MoveToTag(index) => self.move_to_tag(*index)?,
+            WMCommand::ToggleTag(index) => self.toggle_tag(*index)?,
+            WMCommand::FocusNextMonitor => self.focus_monitor(1)?,
+            WMCommand::FocusPreviousMonitor => self.focus_monitor(-1)?,
+            WMCommand::MoveToNextMonitor => self.move_to_monitor(1)?,
+            WMCommand::MoveToPreviousMonitor => self.move_to_monitor(-1)?,
+            WMCommand::ReloadConfig => self.reload_config()?,
+            WMCommand::NextLayout => {
+                let layout = self.current_monitor().layout_manager.layout.cycle(1);
+                self.set_layout(layout)?;
+            }
+            WMCommand::PreviousLayout => {
+                let layout = self.current_monitor().layout_manager.layout.cycle(-1);
+                self.set_layout(layout)?;
+            }
+            WMCommand::SetLayout(layout) => self.set_layout(*layout)?,
+            WMCommand::ChangeMainFactor(delta) => {
+                if self
+                    .current_monitor_mut()
+                    .layout_manager
+                    .change_main_factor(*delta)
+                {
+                    self.arrange()?;
+                }
+            }
+            WMCommand::ChangeInnerGap(delta) => {
+                if self
+                    .current_monitor_mut()
+                    .layout_manager
+                    .change_gaps(*delta, 0)
+                {
+                    self.arrange()?;
+                }
+            }
+            WMCommand::ChangeOuterGap(delta) => {
+                if self
+                    .current_monitor_mut()
+                    .layout_manager
+                    .change_gaps(0, *delta)
+                {
+                    self.arrange()?;
+                }
+            }
+            WMCommand::ToggleGaps => {
+                let layout_manager = &mut self.current_monitor_mut().layout_manager;
+                layout_manager.gaps_enabled = !layout_manager.gaps_enabled;
+                self.arrange()?;
+            }
+            WMCommand::FocusNext => self.focus_stack(1)?,
+            WMCommand::FocusPrevious => self.focus_stack(-1)?,
+            WMCommand::FocusDirection(direction) => self.focus_direction(*direction)?,
+            WMCommand::FocusLast => self.focus_last()?,
+            WMCommand::SwapNext => self.swap_stack(1)?,
+            WMCommand::SwapPrevious => self.swap_stack(-1)?,
+            WMCommand::Zoom => self.zoom()?,
+            WMCommand::ChangeMainCount(delta) => {
+                if self
+                    .current_monitor_mut()
+                    .layout_manager
+                    .change_main_count(*delta)
+                {
+                    self.arrange()?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
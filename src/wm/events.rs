@@ -1,22 +1,26 @@
-use crate::{layout::ReservedEdges, ButtonMapping, WindowState, WindowType};
-use std::{cmp::Reverse, process::Command};
+use crate::{
+    ipc::{Event as IpcEvent, EventKind},
+    tags::ALL_DESKTOPS,
+    ButtonMapping, WindowState,
+};
+use std::cmp::Reverse;
 use x11rb::{
     connection::Connection,
     protocol::{
         xproto::{
             AtomEnum, ButtonPressEvent, ButtonReleaseEvent, ChangeWindowAttributesAux,
-            ClientMessageEvent, ConfigureRequestEvent, ConfigureWindowAux, ConnectionExt,
-            DestroyNotifyEvent, EnterNotifyEvent, ExposeEvent, KeyPressEvent, MapRequestEvent,
-            MotionNotifyEvent, PropMode, SetMode, UnmapNotifyEvent,
+            ClientMessageEvent, ConfigWindow, ConfigureRequestEvent, ConfigureWindowAux,
+            ConnectionExt, DestroyNotifyEvent, EnterNotifyEvent, ExposeEvent, KeyPressEvent,
+            MapRequestEvent, MotionNotifyEvent, Property, PropertyNotifyEvent, SetMode,
+            UnmapNotifyEvent,
         },
         Event,
     },
     rust_connection::ReplyError,
-    wrapper::ConnectionExt as WrapperConnectionExt,
     CURRENT_TIME,
 };
 
-use crate::{util::CommandExt, KeyMapping, WMCommand, XlibError};
+use crate::{layout::Layout, KeyMapping, WMCommand, XlibError};
 
 use super::WM;
 
@@ -60,21 +64,78 @@ impl<'a, C: Connection> WM<'a, C> {
             Event::MotionNotify(event) => self.handle_motion_notify(event)?,
             Event::KeyPress(event) => self.handle_key_press(event)?,
             Event::ClientMessage(event) => self.handle_client_message(event)?,
+            Event::PropertyNotify(event) => self.handle_property_notify(event)?,
+            Event::RandrScreenChangeNotify(_) | Event::RandrNotify(_) => self.update_monitors()?,
             _ => {}
         }
 
         Ok(())
     }
 
-    fn handle_configure_request(&mut self, event: ConfigureRequestEvent) -> Result<(), ReplyError> {
-        // Allow clients to change everything, except sibling / stack mode
-        let aux = ConfigureWindowAux::from_configure_request(&event)
-            .sibling(None)
-            .stack_mode(None);
-        tracing::debug!("configure: {:?}", aux);
+    fn handle_configure_request(&mut self, event: ConfigureRequestEvent) -> Result<(), XlibError> {
+        let Some(win_state) = self.windows.get_window(event.window) else {
+            // Allow unmanaged clients to change everything, except sibling / stack mode
+            let aux = ConfigureWindowAux::from_configure_request(&event)
+                .sibling(None)
+                .stack_mode(None);
+            tracing::debug!("configure: {:?}", aux);
+            self.conn_wrapper
+                .connection
+                .configure_window(event.window, &aux)?;
+            return Ok(());
+        };
+
+        let is_floating = win_state.is_floating
+            || self.monitors[win_state.monitor].layout_manager.layout == Layout::Floating;
+        if !is_floating || win_state.properties.is_fullscreen {
+            // the layout decides where tiled windows go, the client is only told where it is
+            return self.send_configure_notify(event.window);
+        }
+
+        let mask = event.value_mask;
+        let x = if mask.contains(ConfigWindow::X) {
+            event.x
+        } else {
+            win_state.x
+        };
+        let y = if mask.contains(ConfigWindow::Y) {
+            event.y
+        } else {
+            win_state.y
+        };
+        let (width, height) = win_state.size_hints.apply(
+            if mask.contains(ConfigWindow::WIDTH) {
+                event.width
+            } else {
+                win_state.width
+            },
+            if mask.contains(ConfigWindow::HEIGHT) {
+                event.height
+            } else {
+                win_state.height
+            },
+        );
+
+        let aux = ConfigureWindowAux::new()
+            .x(x as i32)
+            .y(y as i32)
+            .width(width as u32)
+            .height(height as u32);
+        tracing::debug!("configure floating: {:?}", aux);
         self.conn_wrapper
             .connection
             .configure_window(event.window, &aux)?;
+
+        if let Some((_, win_state)) = self
+            .windows
+            .get_window_mut_by(|(_, w)| w.window == event.window)
+        {
+            win_state.x = x;
+            win_state.y = y;
+            win_state.width = width;
+            win_state.height = height;
+        }
+
         Ok(())
     }
 
@@ -94,12 +155,18 @@ impl<'a, C: Connection> WM<'a, C> {
     }
 
     fn handle_button_press(&mut self, event: ButtonPressEvent) -> Result<(), XlibError> {
+        if let Some(monitor_index) = self
+            .monitors
+            .iter()
+            .position(|m| m.bar.window == Some(event.event))
+        {
+            return self.handle_bar_click(monitor_index, event.detail, event.event_x);
+        }
+
         let button_mapping = ButtonMapping::new(event.detail, u16::from(event.state));
 
-        if let Some(command) = self.button_mapping.get(&button_mapping) {
+        if let Some(command) = self.button_mapping.get(&button_mapping).cloned() {
             match command {
-                WMCommand::Execute(_) => todo!(),
-                WMCommand::CloseWindow => todo!(),
                 WMCommand::MoveWindow => {
                     if let Some((_, win_state)) = self
                         .windows
@@ -132,14 +199,7 @@ impl<'a, C: Connection> WM<'a, C> {
                         self.focus_window(win_state.window)?;
                     }
 
-                    let screen = self.screen();
-                    if let Some(new_windows) = self.layout_manager.calculate_dimensions(
-                        self.windows.windows(),
-                        screen.width_in_pixels,
-                        screen.height_in_pixels,
-                    ) {
-                        self.apply_layout_diff(new_windows)?;
-                    }
+                    self.arrange()?;
                 }
                 WMCommand::ResizeWindow(_) => {
                     if let Some(win_state) = self.windows.get_window(event.child) {
@@ -167,16 +227,7 @@ impl<'a, C: Connection> WM<'a, C> {
                         self.raise_window(window)?;
                     }
                 }
-                WMCommand::ToggleFullscreen => {
-                    if let Some(win_state) = self.windows.focused() {
-                        if win_state.properties.is_fullscreen {
-                            self.unfullscreen_window(win_state.window)?;
-                        } else {
-                            self.fullscreen_window(win_state.window)?;
-                        }
-                    }
-                }
-                WMCommand::ToggleFloating => todo!(),
+                command => self.run_command(&command)?,
             }
         }
         Ok(())
@@ -188,6 +239,9 @@ impl<'a, C: Connection> WM<'a, C> {
             self.conn_wrapper
                 .connection
                 .change_window_attributes(drag_window.0, &change)?;
+
+            // the window might have been dragged to another monitor
+            self.update_window_monitor(drag_window.0)?;
         }
         if let Some(resize_window) = self.resize_window {
             let change = ChangeWindowAttributesAux::new().cursor(self.cursors.normal);
@@ -204,84 +258,17 @@ impl<'a, C: Connection> WM<'a, C> {
     }
 
     fn handle_key_press(&mut self, event: KeyPressEvent) -> Result<(), XlibError> {
+        if self.resize_mode {
+            return self.handle_resize_mode_key(event.detail);
+        }
+
         let key_mapping = KeyMapping {
             code: event.detail,
             mods: u16::from(event.state),
         };
 
-        if let Some(command) = self.key_mapping.get(&key_mapping) {
-            match command {
-                WMCommand::Execute(command) => {
-                    // TODO: does this work like bash?
-                    let mut command = command.split(' ');
-                    if let Some(program) = command.next() {
-                        if let Err(e) = Command::new(program)
-                            .with_args(command.collect::<Vec<&str>>())
-                            .spawn()
-                        {
-                            tracing::error!("command failed: {e}");
-                        }
-                    }
-                }
-                WMCommand::CloseWindow => {
-                    if let Some(win_state) = &self
-                        .windows
-                        .focused()
-                        .and_then(|fw| self.windows.get_window(fw.window))
-                    {
-                        if win_state.window == self.screen().root {
-                            return Ok(());
-                        }
-
-                        self.send_delete(win_state.window)?;
-                    }
-                }
-                WMCommand::MoveWindow => {
-                    if let Some(win_state) = self.windows.get_window(event.event) {
-                        // only allow moving floating windows with the keyboard
-                        if !win_state.is_floating {
-                            return Ok(());
-                        }
-
-                        if !win_state.can_move() {
-                            return Ok(());
-                        }
-
-                        let change = ChangeWindowAttributesAux::new().cursor(self.cursors.r#move);
-                        self.conn_wrapper
-                            .connection
-                            .change_window_attributes(event.event, &change)?;
-
-                        if self.drag_window.is_none() {
-                            let (x, y) = (-event.event_x, -event.event_y);
-                            self.drag_window = Some((win_state.window, (x, y)));
-                        }
-                    }
-                }
-                WMCommand::ResizeWindow(_factor) => todo!(),
-                WMCommand::ToggleFullscreen => {
-                    if let Some(fw_state) = self.windows.focused() {
-                        if fw_state.properties.is_fullscreen {
-                            self.unfullscreen_window(fw_state.window)?;
-                        } else {
-                            self.fullscreen_window(fw_state.window)?;
-                        }
-                    }
-                }
-                WMCommand::ToggleFloating => {
-                    if let Some(fw_state) = self.windows.focused_mut() {
-                        fw_state.is_floating = !fw_state.is_floating;
-                        let screen = self.screen();
-                        if let Some(new_windows) = self.layout_manager.calculate_dimensions(
-                            self.windows.windows(),
-                            screen.width_in_pixels,
-                            screen.height_in_pixels,
-                        ) {
-                            self.apply_layout_diff(new_windows)?;
-                        }
-                    }
-                }
-            };
+        if let Some(command) = self.key_mapping.get(&key_mapping).cloned() {
+            self.run_command(&command)?;
         }
 
         Ok(())
@@ -290,7 +277,10 @@ impl<'a, C: Connection> WM<'a, C> {
     fn handle_unmap_notify(&mut self, event: UnmapNotifyEvent) -> Result<(), XlibError> {
         if let Some((_, removed_win_state)) = self.windows.remove_window(event.window) {
             let root = self.screen().root;
-            self.unfocus()?;
+            self.emit(IpcEvent::Unmap {
+                window: removed_win_state.window,
+            });
+            self.focus_after_removal(&removed_win_state)?;
 
             self.conn_wrapper
                 .connection
@@ -306,66 +296,9 @@ impl<'a, C: Connection> WM<'a, C> {
                 )
                 .unwrap();
 
-            let screen = self.screen();
-
-            if let Some(new_windows) = self.layout_manager.calculate_dimensions(
-                self.windows.windows(),
-                screen.width_in_pixels,
-                screen.height_in_pixels,
-            ) {
-                self.apply_layout_diff(new_windows)?;
-            }
-        } else if let Some(removed_unmanaged_win_state) =
-            self.windows.remove_unmanaged_window(event.window)
-        {
-            if matches!(removed_unmanaged_win_state.r#type, WindowType::Dock(_)) {
-                if let Some(other_dock) = self
-                    .windows
-                    .unmanaged_windows()
-                    .iter()
-                    .find(|w| matches!(w.r#type, WindowType::Dock(_)))
-                {
-                    match &other_dock.r#type {
-                        WindowType::Dock(reserved) => {
-                            self.layout_manager.reserved = reserved.clone();
-                        }
-                        _ => unreachable!(),
-                    }
-                } else {
-                    self.layout_manager.reserved = ReservedEdges::default();
-
-                    return Ok(());
-                }
-
-                let screen = self.screen();
-
-                if let Some(new_windows) = self.layout_manager.calculate_dimensions(
-                    self.windows.windows(),
-                    screen.width_in_pixels,
-                    screen.height_in_pixels,
-                ) {
-                    self.apply_layout_diff(new_windows)?;
-                }
-            }
-        }
-
-        for bar in self.windows.windows().iter() {
-            if let WindowType::Dock(ReservedEdges {
-                top,
-                right,
-                left,
-                bottom,
-            }) = &bar.r#type
-            {
-                self.layout_manager.reserved.top.width =
-                    self.layout_manager.reserved.top.width.min(top.width);
-                self.layout_manager.reserved.bottom.width =
-                    self.layout_manager.reserved.bottom.width.min(bottom.width);
-                self.layout_manager.reserved.left.width =
-                    self.layout_manager.reserved.left.width.min(left.width);
-                self.layout_manager.reserved.right.width =
-                    self.layout_manager.reserved.right.width.min(right.width);
-            }
+            self.arrange()?;
+        } else {
+            self.remove_dock(event.window)?;
         }
 
         Ok(())
@@ -389,19 +322,23 @@ impl<'a, C: Connection> WM<'a, C> {
             }
         } else if let Some((win, ((width, height), (x, y)))) = self.resize_window {
             let (width, height) = (
-                width as i16 + x + event.event_x,
-                height as i16 + y + event.event_y,
+                (width as i32 + x as i32 + event.event_x as i32).max(1) as u16,
+                (height as i32 + y as i32 + event.event_y as i32).max(1) as u16,
             );
+            let Some((_, win_state)) = self.windows.get_window_mut_by(|(_, w)| w.window == win)
+            else {
+                return Ok(());
+            };
+            let (width, height) = win_state.size_hints.apply(width, height);
+            win_state.width = width;
+            win_state.height = height;
+
             self.conn_wrapper.connection.configure_window(
                 win,
                 &ConfigureWindowAux::new()
                     .width(width as u32)
                     .height(height as u32),
             )?;
-            if let Some((_, win_state)) = self.windows.get_window_mut_by(|(_, w)| w.window == win) {
-                win_state.width = width as u16;
-                win_state.height = height as u16;
-            }
         }
         Ok(())
     }
@@ -431,6 +368,9 @@ impl<'a, C: Connection> WM<'a, C> {
             let root = self.screen().root;
 
             if let Some((_, win_state)) = self.windows.remove_window(event.window) {
+                self.emit(IpcEvent::Unmap {
+                    window: win_state.window,
+                });
                 self.conn_wrapper
                     .connection
                     .change_save_set(SetMode::DELETE, win_state.window)?;
@@ -440,33 +380,80 @@ impl<'a, C: Connection> WM<'a, C> {
                     win_state.x,
                     win_state.y,
                 )?;
+                self.focus_after_removal(&win_state)?;
             }
 
-            let managed: Vec<_> = self.windows.windows().iter().map(|w| w.window).collect();
+            self.arrange()?;
+        } else {
+            self.remove_dock(event.window)?;
+        }
 
-            self.conn_wrapper.connection.change_property32(
-                PropMode::REPLACE,
-                self.screen().root,
-                self.conn_wrapper.atoms._NET_CLIENT_LIST,
-                AtomEnum::WINDOW,
-                managed.as_slice(),
-            )?;
+        Ok(())
+    }
 
-            if let Some(fw) = self.windows.focused() {
-                if fw.window == event.window {
-                    if let Some((next_window_handle, _)) = self.windows.next_window(fw.window) {
-                        self.windows.focus_window(*next_window_handle)?;
-                    }
+    fn handle_property_notify(&mut self, event: PropertyNotifyEvent) -> Result<(), XlibError> {
+        // docks aren't managed, and removing their struts gives the space back
+        if event.atom == self.conn_wrapper.atoms._NET_WM_STRUT_PARTIAL
+            || event.atom == self.conn_wrapper.atoms._NET_WM_STRUT
+        {
+            return self.update_struts(event.window);
+        }
+
+        // the status text, like `xsetroot -name` sets it
+        if event.window == self.screen().root && event.atom == u32::from(AtomEnum::WM_NAME) {
+            return self.update_status();
+        }
+
+        if event.state == Property::DELETE || self.windows.get_window(event.window).is_none() {
+            return Ok(());
+        }
+
+        if event.atom == u32::from(AtomEnum::WM_NORMAL_HINTS) {
+            let size_hints = self.size_hints(event.window)?;
+            if let Some((_, win_state)) = self
+                .windows
+                .get_window_mut_by(|(_, w)| w.window == event.window)
+            {
+                // a window that became fixed size can't be tiled anymore
+                let float = size_hints.is_fixed() && !win_state.is_floating;
+                win_state.size_hints = size_hints;
+                if float {
+                    win_state.is_floating = true;
+                    self.arrange()?;
                 }
             }
-            let screen = self.screen();
+        }
 
-            if let Some(new_windows) = self.layout_manager.calculate_dimensions(
-                self.windows.windows(),
-                screen.width_in_pixels,
-                screen.height_in_pixels,
-            ) {
-                self.apply_layout_diff(new_windows)?;
+        if event.atom == u32::from(AtomEnum::WM_HINTS)
+            || event.atom == self.conn_wrapper.atoms.WM_PROTOCOLS
+        {
+            self.update_wm_hints(event.window)?;
+        }
+
+        if event.atom == u32::from(AtomEnum::WM_NAME)
+            || event.atom == self.conn_wrapper.atoms._NET_WM_NAME
+        {
+            let title = self.window_title(event.window)?;
+            if let Some((_, win_state)) = self
+                .windows
+                .get_window_mut_by(|(_, w)| w.window == event.window)
+            {
+                win_state.title = title.clone();
+            }
+
+            if self.has_subscribers(EventKind::Title) {
+                self.emit(IpcEvent::Title {
+                    window: event.window,
+                    title,
+                });
+            }
+
+            if self
+                .windows
+                .focused()
+                .is_some_and(|fw| fw.window == event.window)
+            {
+                let _ = self.draw_bar();
             }
         }
 
@@ -474,7 +461,16 @@ impl<'a, C: Connection> WM<'a, C> {
     }
 
     fn handle_client_message(&mut self, event: ClientMessageEvent) -> Result<(), XlibError> {
-        if event.type_ == self.conn_wrapper.atoms._NET_WM_STATE {
+        if event.type_ == self.conn_wrapper.atoms.WM_PROTOCOLS {
+            let data = event.data.as_data32();
+
+            // clients answer `_NET_WM_PING` by sending it back to the root window,
+            // with their window in the third field
+            if data[0] == self.conn_wrapper.atoms._NET_WM_PING && event.window == self.screen().root
+            {
+                self.handle_pong(data[2]);
+            }
+        } else if event.type_ == self.conn_wrapper.atoms._NET_WM_STATE {
             let data = event.data.as_data32();
 
             // https://specifications.freedesktop.org/wm-spec/1.3/ar01s05.html#idm45798289450576
@@ -486,31 +482,59 @@ impl<'a, C: Connection> WM<'a, C> {
             let first_property = data[1];
             let second_property = data[2];
 
-            let atoms = self.conn_wrapper.atoms;
-
-            if let Some((_, win_state)) = self
-                .windows
-                .get_window_mut_by(|(_, w)| w.window == event.window)
-            {
-                let action = WindowState::get_property_action(action)?;
-                WindowState::set_window_property(
-                    atoms,
-                    first_property,
-                    &action,
-                    &mut win_state.properties,
-                );
-                WindowState::set_window_property(
-                    atoms,
-                    second_property,
-                    &action,
-                    &mut win_state.properties,
-                );
-                tracing::debug!("new window state: {:?}", win_state);
-            }
+            let action = WindowState::get_property_action(action)?;
+            self.change_net_wm_state(event.window, action, &[first_property, second_property])?;
 
             // whether the source is an application or direct user actions
             // TODO: I don't know what to do with it yet
             // let source_indication = data[3];
+        } else if event.type_ == self.conn_wrapper.atoms._NET_ACTIVE_WINDOW {
+            // https://specifications.freedesktop.org/wm-spec/1.3/ar01s03.html#idm45805407959456
+
+            // 1 = application, 2 = pager, 0 = old clients that don't say
+            let source_indication = event.data.as_data32()[0];
+            let focused = self
+                .windows
+                .focused()
+                .is_some_and(|fw| fw.window == event.window);
+
+            if source_indication == 1 && self.config.focus_stealing_prevention && !focused {
+                self.set_urgent(event.window, true)?;
+            } else {
+                self.activate_window(event.window)?;
+            }
+        } else if event.type_ == self.conn_wrapper.atoms._NET_CURRENT_DESKTOP {
+            // https://specifications.freedesktop.org/wm-spec/1.3/ar01s03.html#idm45798289602512
+            let desktop = event.data.as_data32()[0];
+
+            self.view_tag(desktop as usize)?;
+        } else if event.type_ == self.conn_wrapper.atoms._NET_WM_DESKTOP {
+            // https://specifications.freedesktop.org/wm-spec/1.3/ar01s05.html#idm45798289493680
+            let desktop = event.data.as_data32()[0];
+
+            let tags = self
+                .windows
+                .get_window(event.window)
+                .map(|w| &self.monitors[w.monitor].tags);
+
+            let new_tags = tags.and_then(|tags| {
+                if desktop == ALL_DESKTOPS {
+                    Some(tags.all())
+                } else {
+                    tags.mask(desktop as usize)
+                }
+            });
+
+            if let Some((new_tags, (_, win_state))) = new_tags.zip(
+                self.windows
+                    .get_window_mut_by(|(_, w)| w.window == event.window),
+            ) {
+                win_state.tags = new_tags;
+
+                self.update_window_desktop(event.window)?;
+                self.focus_visible()?;
+                self.arrange()?;
+            }
         }
 
         Ok(())
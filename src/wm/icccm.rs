@@ -0,0 +1,181 @@
+use x11rb::{
+    connection::Connection,
+    properties::WmHints,
+    protocol::xproto::{
+        Atom, AtomEnum, ChangeWindowAttributesAux, ClientMessageEvent, ConnectionExt, EventMask,
+        InputFocus, Window,
+    },
+    CURRENT_TIME,
+};
+
+use crate::{ipc::Event, InputModel, WindowProperties, WindowState, XlibError};
+
+use super::WM;
+
+impl<'a, C: Connection> WM<'a, C> {
+    /// the atoms in `WM_PROTOCOLS`, the messages the client understands
+    pub(crate) fn wm_protocols(&self, window: Window) -> Result<Vec<Atom>, XlibError> {
+        let reply = self
+            .conn_wrapper
+            .connection
+            .get_property(
+                false,
+                window,
+                self.conn_wrapper.atoms.WM_PROTOCOLS,
+                AtomEnum::ATOM,
+                0,
+                u32::MAX,
+            )?
+            .reply()?;
+
+        Ok(reply
+            .value32()
+            .map(|atoms| atoms.collect())
+            .unwrap_or_default())
+    }
+
+    /// sends one of the `WM_PROTOCOLS` messages, like `WM_TAKE_FOCUS`
+    pub(crate) fn send_protocol(&self, window: Window, protocol: Atom) -> Result<(), XlibError> {
+        let event = ClientMessageEvent::new(
+            32,
+            window,
+            self.conn_wrapper.atoms.WM_PROTOCOLS,
+            [protocol, CURRENT_TIME, 0, 0, 0],
+        );
+        self.conn_wrapper
+            .connection
+            .send_event(false, window, EventMask::NO_EVENT, event)?;
+
+        Ok(())
+    }
+
+    /// the `WM_HINTS` of the window, `None` if it doesn't have any
+    fn wm_hints(&self, window: Window) -> Result<Option<WmHints>, XlibError> {
+        let hints = WmHints::get(self.conn_wrapper.connection, window)?.reply();
+
+        // a malformed property is the same as no hints
+        Ok(hints.ok())
+    }
+
+    /// reads the input model and the urgency of the window from `WM_HINTS` and `WM_PROTOCOLS`
+    pub(crate) fn update_wm_hints(&mut self, window: Window) -> Result<(), XlibError> {
+        let hints = self.wm_hints(window)?;
+        let take_focus = self
+            .wm_protocols(window)?
+            .contains(&self.conn_wrapper.atoms.WM_TAKE_FOCUS);
+
+        // clients without an input hint get the focus, like every other window manager does
+        let input = hints.as_ref().and_then(|h| h.input).unwrap_or(true);
+        let urgent = hints.as_ref().is_some_and(|h| h.urgent);
+
+        let focused = self.windows.focused().is_some_and(|fw| fw.window == window);
+        let Some((_, win_state)) = self.windows.get_window_mut_by(|(_, w)| w.window == window)
+        else {
+            return Ok(());
+        };
+        win_state.input_model = InputModel::new(input, take_focus);
+
+        if urgent && focused {
+            // the user is already looking at it
+            if let Some(mut hints) = hints {
+                hints.urgent = false;
+                hints.set(self.conn_wrapper.connection, window)?;
+            }
+            return self.update_urgency(window, |props| props.urgency_hint = false);
+        }
+
+        // only the urgency hint follows `WM_HINTS`, `_NET_WM_STATE_DEMANDS_ATTENTION` stays as it is
+        self.update_urgency(window, |props| props.urgency_hint = urgent)
+    }
+
+    /// marks the window as demanding attention (or not) through `_NET_WM_STATE_DEMANDS_ATTENTION`
+    pub(crate) fn set_urgent(&mut self, window: Window, urgent: bool) -> Result<(), XlibError> {
+        self.update_urgency(window, |props| props.demands_attention = urgent)
+    }
+
+    /// applies a change to the urgency flags of the window, the window is urgent while either
+    /// of them is set, which shows on its border, its `_NET_WM_STATE` and the tags of the bar
+    fn update_urgency(
+        &mut self,
+        window: Window,
+        change: impl FnOnce(&mut WindowProperties),
+    ) -> Result<(), XlibError> {
+        let focused = self.windows.focused().is_some_and(|fw| fw.window == window);
+        let Some((_, win_state)) = self.windows.get_window_mut_by(|(_, w)| w.window == window)
+        else {
+            return Ok(());
+        };
+        let was_urgent = win_state.properties.is_urgent();
+        change(&mut win_state.properties);
+        let urgent = win_state.properties.is_urgent();
+        if was_urgent == urgent {
+            return Ok(());
+        }
+
+        self.conn_wrapper
+            .update_net_wm_state(&win_state.properties, window)?;
+
+        if !focused {
+            let color = if urgent {
+                &self.config.colors.urgent_border
+            } else {
+                &self.config.colors.normal_border
+            };
+            let change = ChangeWindowAttributesAux::new().border_pixel(color.as_argb_u32());
+            self.conn_wrapper
+                .connection
+                .change_window_attributes(window, &change)?;
+        }
+
+        self.emit(Event::Urgent { window, urgent });
+        let _ = self.draw_bar();
+
+        Ok(())
+    }
+
+    /// the window got the focus, so it doesn't need attention anymore,
+    /// the urgency hint is removed from `WM_HINTS` too, like dwm does
+    pub(crate) fn clear_urgency(&mut self, window: Window) -> Result<(), XlibError> {
+        if !self
+            .windows
+            .get_window(window)
+            .is_some_and(|w| w.properties.is_urgent())
+        {
+            return Ok(());
+        }
+
+        if let Some(mut hints) = self.wm_hints(window)?.filter(|h| h.urgent) {
+            hints.urgent = false;
+            hints.set(self.conn_wrapper.connection, window)?;
+        }
+
+        self.update_urgency(window, |props| {
+            props.demands_attention = false;
+            props.urgency_hint = false;
+        })
+    }
+
+    /// gives the keyboard focus to the window, the way its input model asks for
+    pub(crate) fn set_input_focus(&self, win_state: &WindowState) -> Result<(), XlibError> {
+        let model = win_state.input_model;
+
+        let focus = match model {
+            InputModel::Passive | InputModel::LocallyActive => Some(win_state.window),
+            // the client moves the focus itself when it gets `WM_TAKE_FOCUS`
+            InputModel::GloballyActive => None,
+            // windows that never take input shouldn't leave the focus on the previous window
+            InputModel::NoInput => Some(self.screen().root),
+        };
+        if let Some(focus) = focus {
+            self.conn_wrapper
+                .connection
+                .set_input_focus(InputFocus::NONE, focus, CURRENT_TIME)?;
+        }
+
+        if model.takes_focus() {
+            self.send_protocol(win_state.window, self.conn_wrapper.atoms.WM_TAKE_FOCUS)?;
+        }
+
+        Ok(())
+    }
+}
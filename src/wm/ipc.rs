@@ -0,0 +1,177 @@
+use x11rb::connection::Connection;
+
+use crate::{
+    ipc::{
+        Event, EventKind, LayoutInfo, MonitorInfo, Query, Request, Response, TagInfo, WindowInfo,
+    },
+    WMCommand, WindowState, XlibError,
+};
+
+use super::WM;
+
+impl<'a, C: Connection> WM<'a, C> {
+    /// file descriptors of the control socket and its clients
+    pub fn ipc_fds(&self) -> Vec<std::os::unix::io::RawFd> {
+        self.ipc.as_ref().map(|ipc| ipc.fds()).unwrap_or_default()
+    }
+
+    /// handles every pending request on the control socket
+    pub fn handle_ipc(&mut self) -> Result<(), XlibError> {
+        let Some(ipc) = self.ipc.as_mut() else {
+            return Ok(());
+        };
+
+        for (client, request) in ipc.read_requests() {
+            let response = match request {
+                Ok(Request::Subscribe(kinds)) => {
+                    if let Some(ipc) = self.ipc.as_mut() {
+                        ipc.subscribe(client, kinds);
+                    }
+                    Response::Ok(serde_json::Value::Null)
+                }
+                Ok(request) => self.handle_request(request),
+                Err(e) => Response::Error(format!("invalid request: {e}")),
+            };
+
+            if let Some(ipc) = self.ipc.as_mut() {
+                ipc.respond(client, &response);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_request(&mut self, request: Request) -> Response {
+        match request {
+            Request::Command(WMCommand::MoveWindow) => {
+                Response::Error(String::from("this command can only be used from a binding"))
+            }
+            Request::Command(command) => match self.run_command(&command) {
+                Ok(()) => Response::Ok(serde_json::Value::Null),
+                Err(e) => {
+                    tracing::error!("IPC command {command:?} failed: {e}");
+                    Response::Error(e.to_string())
+                }
+            },
+            Request::Query(query) => match self.query(query) {
+                Ok(value) => Response::Ok(value),
+                Err(e) => Response::Error(e.to_string()),
+            },
+            Request::Subscribe(_) => unreachable!("handled by handle_ipc"),
+        }
+    }
+
+    /// whether any IPC client wants events of this kind,
+    /// to skip building events that nobody receives
+    pub(crate) fn has_subscribers(&self, kind: EventKind) -> bool {
+        self.ipc
+            .as_ref()
+            .is_some_and(|ipc| ipc.has_subscribers(kind))
+    }
+
+    /// sends an event to the subscribed IPC clients
+    pub(crate) fn emit(&mut self, event: Event) {
+        if let Some(ipc) = self.ipc.as_mut() {
+            ipc.emit(&event);
+        }
+    }
+
+    fn query(&self, query: Query) -> Result<serde_json::Value, XlibError> {
+        let value = match query {
+            Query::Windows => {
+                let windows = self
+                    .windows
+                    .windows()
+                    .into_iter()
+                    .map(|w| self.window_info(w))
+                    .collect::<Result<Vec<_>, _>>()?;
+                serde_json::to_value(windows)
+            }
+            Query::Focused => {
+                let focused = self
+                    .windows
+                    .focused()
+                    .map(|w| self.window_info(w))
+                    .transpose()?;
+                serde_json::to_value(focused)
+            }
+            Query::Layout => serde_json::to_value(LayoutInfo {
+                monitor: self.selected_monitor,
+                layout: self.current_monitor().layout_manager.layout.name(),
+            }),
+            Query::Tags => {
+                let monitor = self.current_monitor();
+                let occupied = self
+                    .windows
+                    .windows()
+                    .into_iter()
+                    .filter(|w| w.monitor == self.selected_monitor)
+                    .fold(0, |acc, w| acc | w.tags);
+                let urgent = self.urgent_tags(self.selected_monitor);
+
+                let tags: Vec<TagInfo> = monitor
+                    .tags
+                    .names()
+                    .iter()
+                    .enumerate()
+                    .map(|(index, name)| TagInfo {
+                        index,
+                        name: name.clone(),
+                        selected: monitor.tags.selected() & (1 << index) != 0,
+                        occupied: occupied & (1 << index) != 0,
+                        urgent: urgent & (1 << index) != 0,
+                    })
+                    .collect();
+                serde_json::to_value(tags)
+            }
+            Query::Monitors => {
+                let monitors: Vec<MonitorInfo> = self
+                    .monitors
+                    .iter()
+                    .enumerate()
+                    .map(|(index, monitor)| MonitorInfo {
+                        index,
+                        output: monitor.output.clone(),
+                        x: monitor.bounding_box.x,
+                        y: monitor.bounding_box.y,
+                        width: monitor.bounding_box.width,
+                        height: monitor.bounding_box.height,
+                        focused: index == self.selected_monitor,
+                        layout: monitor.layout_manager.layout.name(),
+                        tags: monitor.tags.selected(),
+                    })
+                    .collect();
+                serde_json::to_value(monitors)
+            }
+        };
+
+        // serializing these structs can't fail
+        Ok(value.unwrap_or_default())
+    }
+
+    pub(crate) fn window_info(&self, win_state: &WindowState) -> Result<WindowInfo, XlibError> {
+        let (instance, class) = self.window_class(win_state.window)?;
+        let focused = self
+            .windows
+            .focused()
+            .is_some_and(|fw| fw.window == win_state.window);
+
+        Ok(WindowInfo {
+            id: win_state.window,
+            title: win_state.title.clone(),
+            instance,
+            class,
+            monitor: win_state.monitor,
+            tags: win_state.tags,
+            x: win_state.x,
+            y: win_state.y,
+            width: win_state.width,
+            height: win_state.height,
+            floating: win_state.is_floating,
+            fullscreen: win_state.properties.is_fullscreen,
+            focused,
+            urgent: win_state.properties.is_urgent(),
+            hung: win_state.is_hung,
+        })
+    }
+}
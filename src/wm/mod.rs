@@ -2,24 +2,28 @@ use crate::{
     atoms::Atoms,
     bar::BAR_HEIGHT,
     connection_wrapper::ConnWrapper,
-    layout::{EdgeDimensions, Layout, LayoutManager, ReservedEdges, TiledLayout, WindowStateDiff},
+    font::Fonts,
+    ipc::{socket_path, Event, IpcServer, SOCKET_ENV},
+    layout::{Layout, TiledLayout, WindowStateDiff},
+    tags::{first_tag, TagMask, Tags, ALL_DESKTOPS},
     windows::{WindowHandle, Windows},
-    ButtonMapping, WindowType, RGBA,
+    BoundingBox, ButtonMapping, Monitor, PropertyAction, WindowType,
 };
-use core::marker::PhantomData;
 use std::{
     cmp::Reverse,
     collections::{BinaryHeap, HashMap, HashSet},
+    time::Instant,
 };
 use x11rb::{
     connection::Connection,
     protocol::{
         glx::Window,
+        randr::{ConnectionExt as RandrConnectionExt, NotifyMask},
         xproto::{
-            AtomEnum, ChangeWindowAttributesAux, ClientMessageEvent, ConfigureWindowAux,
-            ConnectionExt, CreateGCAux, CreateWindowAux, Cursor, EventMask, FontDraw, Gcontext,
-            GetGeometryReply, GrabMode, InputFocus, MapNotifyEvent, MapState, ModMask, PropMode,
-            Screen, SetMode, StackMode, Timestamp, WindowClass,
+            AtomEnum, ButtonIndex, ChangeGCAux, ChangeWindowAttributesAux, ConfigureWindowAux,
+            ConnectionExt, CreateGCAux, CreateWindowAux, Cursor, EventMask, Gcontext,
+            GetGeometryReply, Grab, GrabMode, InputFocus, Keycode, MapNotifyEvent, MapState,
+            ModMask, PropMode, Screen, SetMode, StackMode, Timestamp, WindowClass,
         },
         ErrorKind,
     },
@@ -28,9 +32,17 @@ use x11rb::{
     COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME, NONE,
 };
 
-use crate::{Bar, BarPosition, Config, KeyMapping, WMCommand, WindowState, XlibError};
+use crate::{Config, Direction, KeyMapping, WMCommand, WindowState, XlibError};
 
+mod close;
+mod commands;
 mod events;
+mod icccm;
+mod ipc;
+mod resize;
+mod rules;
+mod state;
+mod struts;
 
 pub const LEFT_PTR: u16 = 68;
 pub const SIZING: u16 = 120;
@@ -45,32 +57,42 @@ pub struct Cursors {
     pub hand: Cursor,
 }
 
-pub struct Font {
-    xfont: x11rb::protocol::xproto::Font,
-    height: i32,
-}
-
 type WindowPosition = (i16, i16);
 type WindowSize = (u16, u16);
 
 pub struct WM<'a, C: Connection> {
     pub conn_wrapper: ConnWrapper<'a, C>,
     pub cursors: Cursors,
-    pub fonts: Vec<Font>,
+    /// the fonts the bar text is drawn with
+    pub fonts: Fonts,
     pub screen_num: usize,
     pending_expose: HashSet<Window>,
+    /// the client lists or the work area changed since they were last written to the root window
+    root_properties_dirty: bool,
+    /// the `WM_NAME` of the root window, shown on the right of the bar like dwm does
+    pub status_text: String,
     pub windows: Windows,
     pub black_gc: Gcontext,
     pub sequences_to_ignore: BinaryHeap<Reverse<u16>>,
-    pub bar: Bar<'a, C>,
+    pub monitors: Vec<Monitor<'a, C>>,
+    pub selected_monitor: usize,
+    /// the setup's screen size is not updated when the screen changes, so we keep our own
+    pub screen_size: WindowSize,
     drag_window: Option<(Window, WindowPosition)>,
     resize_window: Option<(Window, (WindowSize, WindowPosition))>,
     pointer_grabbed: bool,
-    config: Config,
+    pub(crate) config: Config,
     key_mapping: HashMap<KeyMapping, WMCommand>,
+    /// the unshifted keysym of every keycode, for keys that aren't grabbed as hotkeys
+    keysyms: HashMap<Keycode, u32>,
     button_mapping: HashMap<ButtonMapping, WMCommand>,
     last_timestamp: Timestamp,
-    layout_manager: LayoutManager,
+    /// the keyboard is grabbed and keys resize the focused window
+    resize_mode: bool,
+    /// windows sent a `_NET_WM_PING` that didn't answer yet, and when it was sent
+    pings: HashMap<Window, Instant>,
+    /// the control socket, `None` if it couldn't be created
+    ipc: Option<IpcServer>,
 }
 
 impl<'a, C: Connection> WM<'a, C> {
@@ -95,58 +117,12 @@ impl<'a, C: Connection> WM<'a, C> {
         )?;
 
         let black_gc = connection.generate_id()?;
-        let font = connection.generate_id()?;
-
-        if let Err(e) = connection.open_font(font, b"6x13")?.check() {
-            tracing::error!("failed to open font {e}");
-            println!("DIR  MIN  MAX EXIST DFLT PROP ASC DESC NAME");
-
-            for reply in connection.list_fonts_with_info(u16::max_value(), b"*")? {
-                let reply = reply?;
-
-                let dir = if reply.draw_direction == FontDraw::LEFT_TO_RIGHT {
-                    "-->"
-                } else if reply.draw_direction == FontDraw::RIGHT_TO_LEFT {
-                    "<--"
-                } else {
-                    "???"
-                };
-
-                let (min, max, indicator) = if reply.min_byte1 == 0 && reply.max_byte1 == 0 {
-                    (reply.min_char_or_byte2, reply.max_char_or_byte2, ' ')
-                } else {
-                    (u16::from(reply.min_byte1), u16::from(reply.max_byte1), '*')
-                };
-
-                let all = if reply.all_chars_exist { "all" } else { "some" };
-
-                let name = String::from_utf8_lossy(&reply.name);
-
-                println!(
-                    "{} {}{:3} {}{:3} {:>5} {:4} {:4} {:3} {:4} {}",
-                    dir,
-                    indicator,
-                    min,
-                    indicator,
-                    max,
-                    all,
-                    reply.default_char,
-                    reply.properties.len(),
-                    reply.font_ascent,
-                    reply.font_descent,
-                    name
-                );
-            }
-            std::process::exit(1);
-        }
-
         let gc_aux = CreateGCAux::new()
             .graphics_exposures(0)
-            .background(screen.white_pixel)
-            .foreground(screen.black_pixel)
-            .font(font);
+            .background(config.colors.bar_background.as_argb_u32())
+            .foreground(config.colors.bar_foreground.as_argb_u32());
         connection.create_gc(black_gc, screen.root, &gc_aux)?;
-        connection.close_font(font)?;
+        let fonts = Fonts::load(&config.bar.fonts, config.bar.font_size);
 
         let font = connection.generate_id()?;
         connection.open_font(font, b"cursor")?;
@@ -208,7 +184,7 @@ impl<'a, C: Connection> WM<'a, C> {
             u16::MAX,
         )?;
 
-        Ok(WM {
+        let mut wm = WM {
             conn_wrapper: ConnWrapper {
                 connection,
                 atoms: Atoms::new(connection)?.reply()?,
@@ -220,38 +196,219 @@ impl<'a, C: Connection> WM<'a, C> {
                 r#move,
                 hand,
             },
-            fonts: vec![],
+            fonts,
             screen_num,
             windows: Windows::new(),
             black_gc,
             sequences_to_ignore: Default::default(),
-            bar: Bar {
-                window: None,
-                show: true,
-                pos: BarPosition::Top,
-                y: 0,
-                x: 0,
-                status_text: String::new(),
-                height: BAR_HEIGHT,
-                width: screen.width_in_pixels,
-                _phantom_data: PhantomData,
-            },
+            monitors: Vec::new(),
+            selected_monitor: 0,
+            screen_size: (screen.width_in_pixels, screen.height_in_pixels),
             pending_expose: Default::default(),
+            root_properties_dirty: true,
+            status_text: String::new(),
             drag_window: None,
             resize_window: None,
             config,
             key_mapping: HashMap::new(),
+            keysyms: HashMap::new(),
             button_mapping: HashMap::new(),
             pointer_grabbed: false,
             last_timestamp: CURRENT_TIME,
-            layout_manager: LayoutManager {
-                layout: Layout::Tiled(TiledLayout::MainStack),
-                reserved: ReservedEdges::default(),
-            },
-        })
+            resize_mode: false,
+            pings: HashMap::new(),
+            ipc: None,
+        };
+
+        wm.monitors = wm.fetch_monitors();
+
+        Ok(wm)
+    }
+
+    /// queries RandR for the connected monitors,
+    /// falls back to a single monitor covering the whole screen
+    fn fetch_monitors(&self) -> Vec<Monitor<'a, C>> {
+        let mut monitors = match Monitor::get_monitors(self) {
+            Ok(monitors) => monitors,
+            Err(e) => {
+                tracing::error!("failed to get monitors: {e}");
+                Vec::new()
+            }
+        };
+
+        if monitors.is_empty() {
+            let (screen_width, screen_height) = self.screen_size;
+            let mut monitor = Monitor::with_bbox(
+                BoundingBox::new(0, 0, screen_width, screen_height),
+                BAR_HEIGHT,
+            );
+            monitor.root = self.screen().root;
+            monitors.push(monitor);
+        }
+
+        for monitor in monitors.iter_mut() {
+            monitor.tags = Tags::new(self.config.tags.clone());
+            monitor.layout_manager.border_width = self.config.border_width;
+            monitor.layout_manager.layout = self.config.layout.default;
+            monitor.layout_manager.main_factor = self.config.layout.main_factor;
+            monitor.layout_manager.main_count = self.config.layout.main_count;
+            monitor.layout_manager.gaps = self.config.layout.gaps;
+            monitor.layout_manager.smart_gaps = self.config.layout.smart_gaps;
+            monitor.layout_manager.size_hints = self.config.layout.size_hints;
+            monitor.bar.show = self.config.bar.show;
+            monitor.bar.pos = self.config.bar.position;
+            monitor.bar.height = self.config.bar.height;
+            monitor.bar.update_position(&monitor.bounding_box);
+        }
+
+        monitors
+    }
+
+    /// rebuilds the monitor list after the screen configuration changed.
+    ///
+    /// monitors that still exist keep their tags and layout,
+    /// windows on monitors that disappeared are moved to the first monitor
+    pub(crate) fn update_monitors(&mut self) -> Result<(), XlibError> {
+        let root_geometry = self
+            .conn_wrapper
+            .connection
+            .get_geometry(self.screen().root)?
+            .reply()?;
+        let screen_size_changed = self.screen_size != (root_geometry.width, root_geometry.height);
+        self.screen_size = (root_geometry.width, root_geometry.height);
+
+        let mut new_monitors = self.fetch_monitors();
+
+        // RandR sends multiple events for a single change
+        let monitors_changed = new_monitors.len() != self.monitors.len()
+            || new_monitors
+                .iter()
+                .zip(self.monitors.iter())
+                .any(|(new, old)| new.bounding_box != old.bounding_box || new.output != old.output);
+        if !monitors_changed && !screen_size_changed {
+            return Ok(());
+        }
+
+        tracing::info!("screen configuration changed, updating monitors");
+
+        let old_monitors = std::mem::take(&mut self.monitors);
+        let had_bars = old_monitors.iter().any(|m| m.bar.window.is_some());
+
+        // maps the index of an old monitor to the index of the new monitor that replaces it
+        let mut mapping = Vec::with_capacity(old_monitors.len());
+        let mut old_bounding_boxes = Vec::with_capacity(old_monitors.len());
+
+        for old in old_monitors {
+            let new_index = new_monitors.iter().position(|m| m.output == old.output);
+
+            if let Some(new_index) = new_index {
+                let new = &mut new_monitors[new_index];
+                new.tags = old.tags;
+                // reserved space is recalculated from the docks below
+                new.layout_manager = old.layout_manager;
+            }
+
+            if let Some(bar_window) = old.bar.window {
+                self.windows.remove_unmanaged_window(bar_window);
+                self.conn_wrapper.connection.destroy_window(bar_window)?;
+            }
+
+            mapping.push(new_index);
+            old_bounding_boxes.push(old.bounding_box);
+        }
+
+        self.monitors = new_monitors;
+        self.check_rule_monitors();
+        self.selected_monitor = mapping
+            .get(self.selected_monitor)
+            .copied()
+            .flatten()
+            .unwrap_or_default();
+
+        for win_state in self.windows.windows_mut() {
+            let old_monitor = win_state.monitor;
+            let new_monitor = mapping.get(old_monitor).copied().flatten();
+
+            let target = new_monitor.unwrap_or_default();
+            win_state.monitor = target;
+
+            if new_monitor.is_none() {
+                win_state.tags = self.monitors[target].tags.selected();
+            }
+
+            if win_state.is_floating {
+                // keep the same position relative to the monitor
+                if let Some(old_bbox) = old_bounding_boxes.get(old_monitor) {
+                    let new_bbox = &self.monitors[target].bounding_box;
+                    win_state.keep_relative_position(old_bbox, new_bbox);
+                }
+            }
+        }
+
+        self.reserve_docks_space();
+
+        if had_bars {
+            self.create_bars()?;
+        }
+
+        // fullscreen windows should cover their new monitor
+        for win_state in self.windows.windows() {
+            if !win_state.properties.is_fullscreen {
+                continue;
+            }
+
+            let monitor_bbox = &self.monitors[win_state.monitor].bounding_box;
+            let configure = ConfigureWindowAux::new()
+                .width(monitor_bbox.width as u32)
+                .height(monitor_bbox.height as u32)
+                .x(monitor_bbox.x as i32)
+                .y(monitor_bbox.y as i32);
+            self.conn_wrapper
+                .connection
+                .configure_window(win_state.window, &configure)?;
+        }
+
+        self.update_desktop_geometry()?;
+        self.update_desktop_properties()?;
+        self.focus_visible()?;
+        self.arrange()?;
+
+        Ok(())
+    }
+
+    pub fn current_monitor(&self) -> &Monitor<'a, C> {
+        &self.monitors[self.selected_monitor]
+    }
+
+    pub fn current_monitor_mut(&mut self) -> &mut Monitor<'a, C> {
+        &mut self.monitors[self.selected_monitor]
+    }
+
+    /// index of the monitor containing the provided point,
+    /// or the selected monitor if there is none
+    fn monitor_at(&self, x: i16, y: i16) -> usize {
+        self.monitors
+            .iter()
+            .position(|m| m.bounding_box.contains(x, y))
+            .unwrap_or(self.selected_monitor)
+    }
+
+    /// index of the monitor containing the center of the provided geometry
+    fn monitor_for_geometry(&self, x: i16, y: i16, width: u16, height: u16) -> usize {
+        // a window near the end of the coordinate space would overflow its center
+        let center_x = (x as i32 + width as i32 / 2).clamp(i16::MIN as i32, i16::MAX as i32);
+        let center_y = (y as i32 + height as i32 / 2).clamp(i16::MIN as i32, i16::MAX as i32);
+        self.monitor_at(center_x as i16, center_y as i16)
+    }
+
+    /// index of the monitor `offset` monitors away from the selected one, wrapping around
+    fn relative_monitor(&self, offset: isize) -> usize {
+        let len = self.monitors.len() as isize;
+        (self.selected_monitor as isize + offset).rem_euclid(len) as usize
     }
 
     pub fn setup(&mut self) -> Result<(), XlibError> {
+        self.check_rule_monitors();
         let screen = self.screen();
 
         let change = ChangeWindowAttributesAux::default()
@@ -259,8 +416,9 @@ impl<'a, C: Connection> WM<'a, C> {
                 EventMask::SUBSTRUCTURE_REDIRECT
                     | EventMask::SUBSTRUCTURE_NOTIFY
                     | EventMask::BUTTON_PRESS
-                    | EventMask::BUTTON_RELEASE, // | EventMask::STRUCTURE_NOTIFY,
-                                                 // | EventMask::POINTER_MOTION,
+                    | EventMask::BUTTON_RELEASE
+                    | EventMask::PROPERTY_CHANGE, // | EventMask::STRUCTURE_NOTIFY,
+                                                  // | EventMask::POINTER_MOTION,
             )
             .cursor(self.cursors.normal);
 
@@ -285,7 +443,25 @@ impl<'a, C: Connection> WM<'a, C> {
         self.button_mapping();
 
         self.grab_hotkeys()?;
-        self.grab_buttons();
+        self.grab_buttons()?;
+
+        self.conn_wrapper.connection.randr_select_input(
+            self.screen().root,
+            NotifyMask::SCREEN_CHANGE | NotifyMask::CRTC_CHANGE | NotifyMask::OUTPUT_CHANGE,
+        )?;
+
+        let socket_path = socket_path();
+        match IpcServer::bind(&socket_path) {
+            Ok(server) => {
+                tracing::info!("listening for IPC on {}", server.path().display());
+                // lets the programs we start find the socket
+                std::env::set_var(SOCKET_ENV, server.path());
+                self.ipc = Some(server);
+            }
+            Err(e) => {
+                tracing::error!("failed to create IPC socket {}: {e}", socket_path.display());
+            }
+        }
 
         Ok(())
     }
@@ -357,32 +533,7 @@ impl<'a, C: Connection> WM<'a, C> {
             )?
             .check()?;
 
-        self.conn_wrapper
-            .connection
-            .delete_property(screen.root, self.conn_wrapper.atoms._NET_CLIENT_LIST)?;
-
-        self.conn_wrapper
-            .connection
-            .change_property32(
-                PropMode::REPLACE,
-                screen.root,
-                self.conn_wrapper.atoms._NET_NUMBER_OF_DESKTOPS,
-                AtomEnum::CARDINAL,
-                // TODO: change this when tags are added
-                &[1],
-            )?
-            .check()?;
-
-        self.conn_wrapper
-            .connection
-            .change_property32(
-                PropMode::REPLACE,
-                screen.root,
-                self.conn_wrapper.atoms._NET_CURRENT_DESKTOP,
-                AtomEnum::CARDINAL,
-                &[0],
-            )?
-            .check()?;
+        self.update_desktop_properties()?;
 
         self.conn_wrapper.connection.change_property32(
             PropMode::REPLACE,
@@ -392,44 +543,91 @@ impl<'a, C: Connection> WM<'a, C> {
             &[0; 2],
         )?;
 
+        self.update_desktop_geometry()?;
+
+        self.update_active_window(None)?;
+
+        self.conn_wrapper.connection.map_window(win_id)?.check()?;
+
+        Ok(())
+    }
+
+    /// sets `_NET_DESKTOP_GEOMETRY`
+    fn update_desktop_geometry(&self) -> Result<(), XlibError> {
+        let (screen_width, screen_height) = self.screen_size;
+
         self.conn_wrapper.connection.change_property32(
             PropMode::REPLACE,
-            screen.root,
+            self.screen().root,
             self.conn_wrapper.atoms._NET_DESKTOP_GEOMETRY,
             AtomEnum::CARDINAL,
-            &[
-                screen.width_in_pixels as u32,
-                screen.height_in_pixels as u32,
-            ],
+            &[screen_width as u32, screen_height as u32],
         )?;
 
+        Ok(())
+    }
+
+    /// sets `_NET_CLIENT_LIST`, `_NET_CLIENT_LIST_STACKING` and `_NET_WORKAREA`,
+    /// called from [`WM::refresh`] after anything marked them as changed
+    fn update_root_properties(&self) -> Result<(), XlibError> {
+        let root = self.screen().root;
+
         self.conn_wrapper.connection.change_property32(
             PropMode::REPLACE,
-            screen.root,
-            self.conn_wrapper.atoms._NET_WORKAREA,
-            AtomEnum::CARDINAL,
-            &[
-                0,
-                self.layout_manager.reserved.top.width,
-                screen.width_in_pixels as u32
-                    - self.layout_manager.reserved.right.width
-                    - self.layout_manager.reserved.left.width,
-                screen.height_in_pixels as u32
-                    - self.layout_manager.reserved.top.width
-                    - self.layout_manager.reserved.bottom.width,
-            ],
+            root,
+            self.conn_wrapper.atoms._NET_CLIENT_LIST,
+            AtomEnum::WINDOW,
+            self.windows.mapping_order(),
+        )?;
+
+        // the children of the root window are in stacking order, bottom to top
+        let stacking: Vec<Window> = self
+            .conn_wrapper
+            .connection
+            .query_tree(root)?
+            .reply()?
+            .children
+            .into_iter()
+            .filter(|&w| self.windows.get_window(w).is_some())
+            .collect();
+        self.conn_wrapper.connection.change_property32(
+            PropMode::REPLACE,
+            root,
+            self.conn_wrapper.atoms._NET_CLIENT_LIST_STACKING,
+            AtomEnum::WINDOW,
+            &stacking,
         )?;
 
+        // there's a single work area for every desktop, so it covers the usable area
+        // of every monitor
+        let workarea = self
+            .monitors
+            .iter()
+            .map(|m| m.layout_manager.usable_area(&m.bounding_box))
+            .reduce(|a, b| {
+                let x = a.x.min(b.x);
+                let y = a.y.min(b.y);
+                let right = (a.x as i32 + a.width as i32).max(b.x as i32 + b.width as i32);
+                let bottom = (a.y as i32 + a.height as i32).max(b.y as i32 + b.height as i32);
+                BoundingBox::new(x, y, (right - x as i32) as u16, (bottom - y as i32) as u16)
+            })
+            .unwrap_or_default();
+        let desktops = self.current_monitor().tags.len().max(1);
+        let workareas: Vec<u32> = [
+            workarea.x as u32,
+            workarea.y as u32,
+            workarea.width as u32,
+            workarea.height as u32,
+        ]
+        .repeat(desktops);
         self.conn_wrapper.connection.change_property32(
             PropMode::REPLACE,
-            screen.root,
-            self.conn_wrapper.atoms._NET_ACTIVE_WINDOW,
+            root,
+            self.conn_wrapper.atoms._NET_WORKAREA,
             AtomEnum::CARDINAL,
-            &[],
+            &workareas,
         )?;
 
-        self.conn_wrapper.connection.map_window(win_id)?.check()?;
-
         Ok(())
     }
 
@@ -480,15 +678,31 @@ impl<'a, C: Connection> WM<'a, C> {
             }
         }
 
+        // whatever is left isn't on the current keyboard layout
+        let mut missing: Vec<u32> = hotkeys.iter().map(|hk| hk.keysym).collect();
+        missing.sort_unstable();
+        missing.dedup();
+        for keysym in missing {
+            tracing::warn!("no keycode for keysym {keysym:#x}, skipping its bindings");
+        }
+
         self.key_mapping = map;
+        self.keysyms = mapping
+            .keysyms
+            .chunks(mapping.keysyms_per_keycode.max(1) as usize)
+            .enumerate()
+            .map(|(index, keysyms)| (lo + index as u8, keysyms[0]))
+            .collect();
         Ok(())
     }
 
+    /// grabs every mapped key, a key that can't be grabbed is logged and skipped
     fn grab_hotkeys(&mut self) -> Result<(), XlibError> {
         let screen = self.screen();
 
-        self.key_mapping.keys().for_each(|hk| {
-            self.conn_wrapper
+        for hk in self.key_mapping.keys() {
+            let res = self
+                .conn_wrapper
                 .connection
                 .grab_key(
                     true,
@@ -497,11 +711,17 @@ impl<'a, C: Connection> WM<'a, C> {
                     hk.code,
                     GrabMode::ASYNC,
                     GrabMode::ASYNC,
-                )
-                .unwrap()
-                .check()
-                .unwrap();
-        });
+                )?
+                .check();
+
+            if let Err(err) = res {
+                tracing::error!(
+                    "failed to grab key {} with modifiers {:#x}: {err}",
+                    hk.code,
+                    hk.mods
+                );
+            }
+        }
 
         Ok(())
     }
@@ -521,10 +741,13 @@ impl<'a, C: Connection> WM<'a, C> {
                 });
     }
 
-    fn grab_buttons(&self) {
+    /// grabs every mapped button, a button that can't be grabbed is logged and skipped
+    fn grab_buttons(&self) -> Result<(), XlibError> {
         let screen = self.screen();
-        self.button_mapping.keys().for_each(|m| {
-            self.conn_wrapper
+
+        for m in self.button_mapping.keys() {
+            let res = self
+                .conn_wrapper
                 .connection
                 .grab_button(
                     false,
@@ -536,85 +759,134 @@ impl<'a, C: Connection> WM<'a, C> {
                     NONE,
                     m.button,
                     m.mods,
-                )
-                .unwrap()
-                .check()
-                .unwrap();
-        })
-    }
-
-    // pub fn load_font(&mut self, xft: &Xft, fontname: &str) -> *mut XftFont {
-    //     unsafe {
-    //         let xfont = (xft.XftFontOpenName)(
-    //             self.display,
-    //             self.screen,
-    //             CString::new(fontname).unwrap_or_default().as_ptr(),
-    //         );
-    //         self.fonts.push(Font {
-    //             xfont,
-    //             height: (*xfont).ascent + (*xfont).descent,
-    //         });
-    //         xfont
-    //     }
-    // }
-
-    // pub fn text_width(
-    //     &self,
-    //     xft: &Xft,
-    //     max_width: u32,
-    //     left_pad: u32,
-    //     text: &str,
-    // ) -> Result<u32, XlibError> {
-    //     // if (!drw || (render && (!drw->scheme || !w)) || !text || !drw->fonts)
-    //     if self.fonts.len() < 1 {
-    //         return Err(XlibError::NoFontLoaded);
-    //     }
-
-    //     let mut x = 0;
-    //     let mut ellipsis_width = 0;
-    //     let mut ellipsis_x = 0;
-    //     let mut ellipsis_len = 0;
-    //     let available_width = 0;
-    //     let extent_x = 0;
-
-    //     for c in text.chars() {
-    //         for font in self.fonts.iter() {
-    //             unsafe {
-    //                 if (xft.XftCharExists)(self.display, font.xfont, c.into()) == xlib::True {
-    //                     let text_len = text.len();
-
-    //                     let (text_width, height) = {
-    //                         let mut glyph_info: XGlyphInfo = std::mem::zeroed();
-    //                         (xft.XftTextExtentsUtf8)(
-    //                             self.display,
-    //                             font.xfont,
-    //                             text.as_ptr(),
-    //                             c.len_utf8().try_into().unwrap(),
-    //                             &mut glyph_info,
-    //                         );
-
-    //                         (glyph_info.width, font.height)
-    //                     };
-
-    //                     if available_width + ellipsis_width <= text_width {
-    //                         ellipsis_x = x + available_width;
-    //                         ellipsis_width = text_width - available_width;
-    //                         ellipsis_len = text_len;
-    //                     }
-
-    //                     if (available_width + text_width) as u32 > max_width {
-    //                         x += text_width;
-    //                     } else {
-    //                     }
-
-    //                     break;
-    //                 }
-    //             }
-    //         }
-    //     }
-
-    //     Ok(0)
-    // }
+                )?
+                .check();
+
+            if let Err(err) = res {
+                tracing::error!(
+                    "failed to grab button {:?} with modifiers {:?}: {err}",
+                    m.button,
+                    m.mods
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn ungrab_bindings(&self) -> Result<(), XlibError> {
+        let root = self.screen().root;
+        self.conn_wrapper
+            .connection
+            .ungrab_key(Grab::ANY, root, ModMask::ANY)?;
+        self.conn_wrapper
+            .connection
+            .ungrab_button(ButtonIndex::ANY, root, ModMask::ANY)?;
+
+        Ok(())
+    }
+
+    /// rereads the config file and applies it to the running session.
+    /// if the file is invalid the current config is kept
+    pub fn reload_config(&mut self) -> Result<(), XlibError> {
+        let Ok(config) = Config::try_load() else {
+            tracing::warn!("keeping the current config");
+            return Ok(());
+        };
+        self.config = config;
+        self.check_rule_monitors();
+
+        self.ungrab_bindings()?;
+        self.key_mapping()?;
+        self.button_mapping();
+        self.grab_hotkeys()?;
+        self.grab_buttons()?;
+        self.fonts = Fonts::load(&self.config.bar.fonts, self.config.bar.font_size);
+
+        let tag_names = self.config.tags.clone();
+        for monitor in self.monitors.iter_mut() {
+            monitor.tags.set_names(tag_names.clone());
+            monitor.layout_manager.border_width = self.config.border_width;
+            monitor.layout_manager.main_factor = self.config.layout.main_factor;
+            monitor.layout_manager.main_count = self.config.layout.main_count;
+            monitor.layout_manager.gaps = self.config.layout.gaps;
+            monitor.layout_manager.smart_gaps = self.config.layout.smart_gaps;
+            monitor.layout_manager.size_hints = self.config.layout.size_hints;
+        }
+
+        let focused = self.windows.focused().map(|w| w.window);
+        for win_state in self.windows.windows_mut() {
+            // windows only tagged with removed tags go to the viewed ones
+            let monitor = &self.monitors[win_state.monitor];
+            win_state.tags &= monitor.tags.all();
+            if win_state.tags == 0 {
+                win_state.tags = monitor.tags.selected();
+            }
+
+            let border_color = if Some(win_state.window) == focused {
+                self.config.colors.focused_border
+            } else {
+                self.config.colors.normal_border
+            };
+            let change = ChangeWindowAttributesAux::new().border_pixel(border_color.as_argb_u32());
+            self.conn_wrapper
+                .connection
+                .change_window_attributes(win_state.window, &change)?;
+
+            let border_width = win_state.border_width(self.config.border_width);
+            if !win_state.properties.is_fullscreen {
+                let configure = ConfigureWindowAux::new().border_width(border_width);
+                self.conn_wrapper
+                    .connection
+                    .configure_window(win_state.window, &configure)?;
+            }
+            self.conn_wrapper.connection.change_property32(
+                PropMode::REPLACE,
+                win_state.window,
+                self.conn_wrapper.atoms._NET_FRAME_EXTENTS,
+                AtomEnum::CARDINAL,
+                &[border_width; 4],
+            )?;
+        }
+
+        let change = ChangeGCAux::new()
+            .foreground(self.config.colors.bar_foreground.as_argb_u32())
+            .background(self.config.colors.bar_background.as_argb_u32());
+        self.conn_wrapper
+            .connection
+            .change_gc(self.black_gc, &change)?;
+        self.set_root_background_color(self.config.colors.background.as_argb_u32())?;
+
+        // bars are recreated so they pick up their new position and size,
+        // and a bar that was hidden before shows up once the config turns it on
+        for monitor_index in 0..self.monitors.len() {
+            if let Some(bar_window) = self.monitors[monitor_index].bar.window.take() {
+                self.windows.remove_unmanaged_window(bar_window);
+                self.conn_wrapper.connection.destroy_window(bar_window)?;
+            }
+
+            let monitor = &mut self.monitors[monitor_index];
+            monitor.bar.show = self.config.bar.show;
+            monitor.bar.pos = self.config.bar.position;
+            monitor.bar.height = self.config.bar.height;
+            monitor.bar.update_position(&monitor.bounding_box);
+        }
+        self.create_bars()?;
+        self.reserve_docks_space();
+
+        let window_handles: Vec<Window> = self.windows.windows().iter().map(|w| w.window).collect();
+        for window in window_handles {
+            self.update_window_desktop(window)?;
+        }
+        self.update_desktop_properties()?;
+        self.update_desktop_geometry()?;
+        self.focus_visible()?;
+        self.arrange()?;
+
+        tracing::info!("config reloaded");
+
+        Ok(())
+    }
 
     /// Scan for already existing windows and manage them
     pub fn scan_windows(&mut self) -> Result<(), XlibError> {
@@ -653,10 +925,10 @@ impl<'a, C: Connection> WM<'a, C> {
         geom: &GetGeometryReply,
     ) -> Result<(), XlibError> {
         tracing::debug!("managing window {:?}", window);
-        assert!(
-            self.windows.get_window(window).is_none(),
-            "Unmanaged window should not exist already!"
-        );
+        if self.windows.get_window(window).is_some() {
+            tracing::warn!("window {window} is already managed");
+            return Ok(());
+        }
         let change = ChangeWindowAttributesAux::new().event_mask(
             EventMask::ENTER_WINDOW
                 | EventMask::FOCUS_CHANGE
@@ -666,46 +938,92 @@ impl<'a, C: Connection> WM<'a, C> {
                 | EventMask::STRUCTURE_NOTIFY,
         );
 
+        let window_type = self.get_window_type(window)?;
+
+        tracing::debug!("window type: {window_type:?}");
+
+        let window_type = window_type.unwrap_or(WindowType::Normal);
+
+        let mut win_state = WindowState::new(window, geom, window_type, false);
+        win_state.title = self.window_title(window)?;
+        win_state.tags = self.current_monitor().tags.selected();
+        win_state.monitor = self.selected_monitor;
+
+        let border_width = win_state.border_width(self.config.border_width);
         self.conn_wrapper.connection.change_property32(
             PropMode::REPLACE,
             window,
             self.conn_wrapper.atoms._NET_FRAME_EXTENTS,
             AtomEnum::CARDINAL,
             // [left, right, top, bottom]
-            &[BORDER_WIDTH; 4],
+            &[border_width; 4],
         )?;
 
-        let window_type = self.get_window_type(window)?;
-
-        tracing::debug!("window type: {window_type:?}");
-
-        let window_type = window_type.unwrap_or(WindowType::Normal);
-
-        let win_state = WindowState::new(window, geom, window_type, false);
-
+        let mut fullscreen = false;
         match &win_state.r#type {
-            WindowType::Dock(ReservedEdges {
-                top,
-                right,
-                left,
-                bottom,
-            }) => {
-                self.layout_manager.reserved.top.width =
-                    self.layout_manager.reserved.top.width.max(top.width);
-                self.layout_manager.reserved.bottom.width =
-                    self.layout_manager.reserved.bottom.width.max(bottom.width);
-                self.layout_manager.reserved.left.width =
-                    self.layout_manager.reserved.left.width.max(left.width);
-                self.layout_manager.reserved.right.width =
-                    self.layout_manager.reserved.right.width.max(right.width);
-
+            WindowType::Dock(_) => {
+                // docks belong to the monitor they are placed on
+                win_state.monitor =
+                    self.monitor_for_geometry(geom.x, geom.y, geom.width, geom.height);
                 self.windows.add_unmanaged_window(win_state);
+
+                // to know when the struts change, the bars already have their own event mask
+                if !self.monitors.iter().any(|m| m.bar.window == Some(window)) {
+                    let change =
+                        ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE);
+                    self.conn_wrapper
+                        .connection
+                        .change_window_attributes(window, &change)?;
+                }
+                self.reserve_docks_space();
             }
             WindowType::Desktop => {
                 self.windows.add_unmanaged_window(win_state);
             }
-            WindowType::Normal => {
+            WindowType::Normal
+            | WindowType::Dialog
+            | WindowType::Utility
+            | WindowType::Splash
+            | WindowType::Menu
+            | WindowType::Toolbar => {
+                // transient windows go wherever their parent is
+                win_state.transient_for = self.transient_for(window)?;
+                if let Some(parent) = win_state
+                    .transient_for
+                    .and_then(|parent| self.windows.get_window(parent))
+                {
+                    win_state.monitor = parent.monitor;
+                    win_state.tags = parent.tags;
+                }
+
+                win_state.size_hints = self.size_hints(window)?;
+
+                // only normal windows are tiled, the rest float in the middle of their parent,
+                // and so do windows that can't be resized
+                if win_state.r#type != WindowType::Normal
+                    || win_state.transient_for.is_some()
+                    || win_state.size_hints.is_fixed()
+                {
+                    win_state.is_floating = true;
+                    self.center_window(&mut win_state);
+                }
+
+                // rules are applied before the window is added, so the layout sees the result
+                let actions = self.window_rules(&win_state)?;
+                self.apply_rule_actions(&mut win_state, &actions)?;
+                fullscreen = actions.fullscreen.unwrap_or(false);
+                // splash screens are only there to be looked at
+                let should_focus = !actions.no_focus.unwrap_or(false)
+                    && win_state.r#type != WindowType::Splash
+                    && self.is_shown(&win_state);
+
+                let is_floating = win_state.is_floating;
                 self.windows.add_window(win_state.window, win_state);
+                self.update_wm_hints(window)?;
+                self.emit(Event::Map { window });
+                if is_floating {
+                    self.raise_window(window)?;
+                }
 
                 if let Some((_, fsw_state)) = self
                     .windows
@@ -715,7 +1033,7 @@ impl<'a, C: Connection> WM<'a, C> {
                     self.conn_wrapper
                         .connection
                         .configure_window(fsw_state.window, &configure)?;
-                } else {
+                } else if should_focus {
                     tracing::debug!("focus mapped window");
                     self.focus_window(window)?;
                     // TODO: move mouse to new window
@@ -739,13 +1057,12 @@ impl<'a, C: Connection> WM<'a, C> {
                     .connection
                     .change_window_attributes(window, &change)?;
 
-                let configure = ConfigureWindowAux::new().border_width(BORDER_WIDTH);
+                let configure = ConfigureWindowAux::new().border_width(border_width);
 
                 self.conn_wrapper
                     .connection
                     .configure_window(window, &configure)?
-                    .check()
-                    .unwrap();
+                    .check()?;
 
                 // Ignore all events caused by reparent_window(). All those events have the sequence number
                 // of the reparent_window() request, thus remember its sequence number. The
@@ -753,35 +1070,24 @@ impl<'a, C: Connection> WM<'a, C> {
                 // in-between, which could cause other events to get the same sequence number.
                 self.sequences_to_ignore
                     .push(Reverse(cookie.sequence_number() as u16));
+
+                self.update_window_desktop(window)?;
             }
-            _ => todo!(),
         }
 
-        let screen = self.screen();
-        if let Some(new_windows) = self.layout_manager.calculate_dimensions(
-            self.windows.windows(),
-            screen.width_in_pixels,
-            screen.height_in_pixels,
-        ) {
-            self.apply_layout_diff(new_windows)?;
+        self.arrange()?;
+        if fullscreen {
+            self.fullscreen_window(window)?;
         }
 
         // after all the layout calculations we map the window
         // this prevents the window from appearing for a moment in a place
         // then moved, which is jarring to see
 
-        let screen = self.screen();
         self.conn_wrapper.connection.grab_server()?;
         self.conn_wrapper
             .connection
             .change_save_set(SetMode::INSERT, window)?;
-        self.conn_wrapper.connection.change_property32(
-            PropMode::APPEND,
-            screen.root,
-            self.conn_wrapper.atoms._NET_CLIENT_LIST,
-            AtomEnum::WINDOW,
-            &[window],
-        )?;
         self.conn_wrapper
             .connection
             .map_window(window)?
@@ -791,28 +1097,652 @@ impl<'a, C: Connection> WM<'a, C> {
         Ok(())
     }
 
-    fn focus_window(&mut self, window_handle: WindowHandle) -> Result<(), XlibError> {
-        if let Some(previos_focus) = self.windows.focus_window(window_handle)? {
-            if previos_focus.window != window_handle {
-                let change =
-                    ChangeWindowAttributesAux::new().border_pixel(RGBA::BLACK.as_argb_u32());
-                self.conn_wrapper
-                    .connection
-                    .change_window_attributes(previos_focus.window, &change)?;
-            }
-        }
-        let change = ChangeWindowAttributesAux::new().border_pixel(RGBA::CYAN.as_argb_u32());
-
-        self.conn_wrapper
+    /// the managed window the window is transient for, like the main window of a dialog
+    pub(crate) fn transient_for(&self, window: Window) -> Result<Option<Window>, XlibError> {
+        let reply = self
+            .conn_wrapper
             .connection
-            .change_window_attributes(window_handle, &change)?;
+            .get_property(
+                false,
+                window,
+                AtomEnum::WM_TRANSIENT_FOR,
+                AtomEnum::WINDOW,
+                0,
+                1,
+            )?
+            .reply()?;
 
-        self.conn_wrapper.connection.set_input_focus(
-            InputFocus::NONE,
-            window_handle,
-            CURRENT_TIME,
+        Ok(reply
+            .value32()
+            .and_then(|mut value| value.next())
+            .filter(|&parent| parent != window && self.windows.get_window(parent).is_some()))
+    }
+
+    /// places a floating window in the middle of its parent, or of its monitor if it has none,
+    /// keeping it inside the usable area of the monitor
+    fn center_window(&self, win_state: &mut WindowState) {
+        let monitor = &self.monitors[win_state.monitor];
+        let usable = monitor.layout_manager.usable_area(&monitor.bounding_box);
+        let area = match win_state
+            .transient_for
+            .and_then(|parent| self.windows.get_window(parent))
+        {
+            Some(parent) => BoundingBox::new(parent.x, parent.y, parent.width, parent.height),
+            None => usable.clone(),
+        };
+
+        let border = win_state.border_width(self.config.border_width) as i32 * 2;
+        let width = win_state.width as i32 + border;
+        let height = win_state.height as i32 + border;
+        let x = area.x as i32 + (area.width as i32 - width) / 2;
+        let y = area.y as i32 + (area.height as i32 - height) / 2;
+
+        // windows bigger than the monitor stick to its top left corner
+        let max_x = (usable.x as i32 + usable.width as i32 - width).max(usable.x as i32);
+        let max_y = (usable.y as i32 + usable.height as i32 - height).max(usable.y as i32);
+        win_state.x = x.clamp(usable.x as i32, max_x) as i16;
+        win_state.y = y.clamp(usable.y as i32, max_y) as i16;
+    }
+
+    /// switches the layout of the selected monitor
+    pub fn set_layout(&mut self, layout: Layout) -> Result<(), XlibError> {
+        self.current_monitor_mut().layout_manager.layout = layout;
+        self.arrange()?;
+        self.emit(Event::Layout {
+            monitor: self.selected_monitor,
+            layout: self
+                .current_monitor()
+                .layout_manager
+                .layout
+                .name()
+                .to_string(),
+        });
+
+        Ok(())
+    }
+
+    /// shows the windows on the selected tags of their monitor, hides the rest,
+    /// then recalculates the layout of the visible ones on every monitor
+    pub fn arrange(&mut self) -> Result<(), XlibError> {
+        self.show_hide()?;
+        // windows, docks or the stacking order might have changed
+        self.root_properties_dirty = true;
+
+        for monitor_index in 0..self.monitors.len() {
+            let monitor = &self.monitors[monitor_index];
+            if let Some(new_windows) = monitor.layout_manager.calculate_dimensions(
+                self.windows
+                    .visible_windows(monitor_index, monitor.tags.selected()),
+                &monitor.bounding_box,
+            ) {
+                self.apply_layout_diff(new_windows)?;
+            }
+        }
+
+        if let Some(fw_state) = self.windows.focused() {
+            self.raise_if_monocle(fw_state.window)?;
+        }
+
+        // the tags, the layout or the windows on them changed
+        let _ = self.draw_bar();
+
+        Ok(())
+    }
+
+    /// tiled windows overlap in the monocle layout, so the focused one has to be on top
+    fn raise_if_monocle(&mut self, window: Window) -> Result<(), XlibError> {
+        let Some(win_state) = self.windows.get_window(window) else {
+            return Ok(());
+        };
+
+        let layout = self.monitors[win_state.monitor].layout_manager.layout;
+        if layout == Layout::Tiled(TiledLayout::Monocle) && !win_state.is_floating {
+            let configure = ConfigureWindowAux::new().stack_mode(StackMode::ABOVE);
+            self.conn_wrapper
+                .connection
+                .configure_window(window, &configure)?;
+            self.root_properties_dirty = true;
+        }
+
+        Ok(())
+    }
+
+    /// whether the window is on the selected tags of its monitor
+    fn is_shown(&self, win_state: &WindowState) -> bool {
+        self.monitors
+            .get(win_state.monitor)
+            .is_some_and(|m| win_state.is_visible_on(m.tags.selected()))
+    }
+
+    /// moves windows that are not on the selected tags out of the screen (like dwm),
+    /// and moves the visible ones back to their last known position
+    fn show_hide(&self) -> Result<(), XlibError> {
+        for win_state in self.windows.windows() {
+            let configure = if !self.is_shown(win_state) {
+                let hidden_x =
+                    -2 * (win_state.width as i32 + (self.config.border_width * 2) as i32);
+                ConfigureWindowAux::new().x(hidden_x).y(win_state.y as i32)
+            } else if win_state.properties.is_fullscreen {
+                let monitor_bbox = &self.monitors[win_state.monitor].bounding_box;
+                ConfigureWindowAux::new()
+                    .x(monitor_bbox.x as i32)
+                    .y(monitor_bbox.y as i32)
+            } else {
+                ConfigureWindowAux::new()
+                    .x(win_state.x as i32)
+                    .y(win_state.y as i32)
+            };
+
+            self.conn_wrapper
+                .connection
+                .configure_window(win_state.window, &configure)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn view_tag(&mut self, index: usize) -> Result<(), XlibError> {
+        let Some(mask) = self.current_monitor().tags.mask(index) else {
+            return Ok(());
+        };
+
+        if self.current_monitor_mut().tags.view(mask) {
+            self.tags_changed()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn toggle_view_tag(&mut self, index: usize) -> Result<(), XlibError> {
+        let Some(mask) = self.current_monitor().tags.mask(index) else {
+            return Ok(());
+        };
+
+        if self.current_monitor_mut().tags.toggle_view(mask) {
+            self.tags_changed()?;
+        }
+
+        Ok(())
+    }
+
+    /// moves the focused window to the tag at the provided index
+    pub fn move_to_tag(&mut self, index: usize) -> Result<(), XlibError> {
+        let Some(mask) = self.current_monitor().tags.mask(index) else {
+            return Ok(());
+        };
+
+        self.set_focused_tags(|_| mask)
+    }
+
+    /// adds or removes the tag at the provided index from the focused window
+    pub fn toggle_tag(&mut self, index: usize) -> Result<(), XlibError> {
+        let Some(mask) = self.current_monitor().tags.mask(index) else {
+            return Ok(());
+        };
+
+        self.set_focused_tags(|tags| tags ^ mask)
+    }
+
+    fn set_focused_tags(&mut self, f: impl FnOnce(u32) -> u32) -> Result<(), XlibError> {
+        let Some(fw_state) = self.windows.focused_mut() else {
+            return Ok(());
+        };
+
+        let new_tags = f(fw_state.tags);
+        // a window must always be on at least one tag
+        if new_tags == 0 || new_tags == fw_state.tags {
+            return Ok(());
+        }
+
+        fw_state.tags = new_tags;
+        let window = fw_state.window;
+
+        self.update_window_desktop(window)?;
+        self.focus_visible()?;
+        self.arrange()?;
+
+        Ok(())
+    }
+
+    /// the tags of the monitor that have a window demanding attention
+    pub(crate) fn urgent_tags(&self, monitor: usize) -> TagMask {
+        self.windows
+            .windows()
+            .into_iter()
+            .filter(|w| w.monitor == monitor && w.properties.is_urgent())
+            .fold(0, |acc, w| acc | w.tags)
+    }
+
+    fn tags_changed(&mut self) -> Result<(), XlibError> {
+        self.focus_visible()?;
+        self.arrange()?;
+        self.update_current_desktop()?;
+        self.emit(Event::Tags {
+            monitor: self.selected_monitor,
+            tags: self.current_monitor().tags.selected(),
+        });
+
+        Ok(())
+    }
+
+    /// moves focus away from a window that is no longer managed, if it had it.
+    /// transient windows give it back to their parent
+    pub(crate) fn focus_after_removal(&mut self, removed: &WindowState) -> Result<(), XlibError> {
+        if self.windows.focused().is_some() {
+            return Ok(());
+        }
+
+        if let Some(parent) = removed.transient_for.filter(|&parent| {
+            self.windows
+                .get_window(parent)
+                .is_some_and(|w| self.is_shown(w))
+        }) {
+            return self.focus_window(parent);
+        }
+
+        self.focus_visible()
+    }
+
+    /// keeps focus on the focused window if it's still visible on the selected monitor,
+    /// otherwise focuses the first visible window of the selected monitor
+    fn focus_visible(&mut self) -> Result<(), XlibError> {
+        if self
+            .windows
+            .focused()
+            .is_some_and(|fw| fw.monitor == self.selected_monitor && self.is_shown(fw))
+        {
+            return Ok(());
+        }
+
+        // the window that was focused last on the monitor gets the focus back
+        if let Some(window) = self
+            .monitor_selected_window(self.selected_monitor)
+            .or_else(|| {
+                self.windows
+                    .visible_windows(
+                        self.selected_monitor,
+                        self.current_monitor().tags.selected(),
+                    )
+                    .first()
+                    .copied()
+            })
+            .map(|w| w.window)
+        {
+            self.focus_window(window)?;
+        } else {
+            self.unfocus()?;
+        }
+
+        Ok(())
+    }
+
+    /// the window that was focused last on the monitor, if it's still there and shown
+    pub(crate) fn monitor_selected_window(&self, monitor_index: usize) -> Option<&WindowState> {
+        self.monitors[monitor_index]
+            .selected_window
+            .and_then(|window| self.windows.get_window(window))
+            .filter(|w| w.monitor == monitor_index && self.is_shown(w))
+    }
+
+    /// visible windows of the selected monitor that are placed by the layout
+    fn tiled_windows(&self) -> Vec<&WindowState> {
+        self.windows
+            .visible_windows(
+                self.selected_monitor,
+                self.current_monitor().tags.selected(),
+            )
+            .into_iter()
+            .filter(|w| w.r#type == WindowType::Normal && !w.is_floating)
+            .collect()
+    }
+
+    /// the window `offset` windows away from the focused one in `windows`, wrapping around
+    fn relative_window(windows: &[&WindowState], focused: Window, offset: isize) -> Option<Window> {
+        let index = windows.iter().position(|w| w.window == focused)?;
+        let target = (index as isize + offset).rem_euclid(windows.len() as isize);
+
+        Some(windows[target as usize].window)
+    }
+
+    /// focuses the visible window `offset` windows away from the focused one
+    pub fn focus_stack(&mut self, offset: isize) -> Result<(), XlibError> {
+        let windows = self.windows.visible_windows(
+            self.selected_monitor,
+            self.current_monitor().tags.selected(),
+        );
+        let target = match self.windows.focused() {
+            Some(fw_state) => Self::relative_window(&windows, fw_state.window, offset),
+            None => windows.first().map(|w| w.window),
+        };
+
+        if let Some(target) = target {
+            self.focus_window(target)?;
+        }
+
+        Ok(())
+    }
+
+    /// focuses the closest visible window in the provided direction,
+    /// measured between the centers of the windows
+    pub fn focus_direction(&mut self, direction: Direction) -> Result<(), XlibError> {
+        let Some(fw_state) = self.windows.focused() else {
+            return self.focus_stack(0);
+        };
+
+        let center = |w: &WindowState| {
+            (
+                w.x as i32 + w.width as i32 / 2,
+                w.y as i32 + w.height as i32 / 2,
+            )
+        };
+        let (fx, fy) = center(fw_state);
+
+        let target = self
+            .windows
+            .visible_windows(
+                self.selected_monitor,
+                self.current_monitor().tags.selected(),
+            )
+            .into_iter()
+            .filter(|w| w.window != fw_state.window)
+            .filter_map(|w| {
+                let (x, y) = center(w);
+                // distance in the direction, and away from the line going in that direction
+                let (along, across) = match direction {
+                    Direction::Left => (fx - x, y - fy),
+                    Direction::Right => (x - fx, y - fy),
+                    Direction::Up => (fy - y, x - fx),
+                    Direction::Down => (y - fy, x - fx),
+                };
+                (along > 0).then_some((w.window, along + 2 * across.abs()))
+            })
+            .min_by_key(|(_, distance)| *distance)
+            .map(|(window, _)| window);
+
+        if let Some(target) = target {
+            self.focus_window(target)?;
+        }
+
+        Ok(())
+    }
+
+    /// focuses the previously focused window if it's still visible
+    pub fn focus_last(&mut self) -> Result<(), XlibError> {
+        let Some(window) = self
+            .windows
+            .previos_focus()
+            .filter(|w| self.is_shown(w))
+            .map(|w| w.window)
+        else {
+            return Ok(());
+        };
+
+        self.focus_window(window)
+    }
+
+    /// swaps the focused window with the tiled window `offset` windows away from it
+    pub fn swap_stack(&mut self, offset: isize) -> Result<(), XlibError> {
+        let Some(focused) = self.windows.focused().map(|w| w.window) else {
+            return Ok(());
+        };
+
+        if let Some(target) = Self::relative_window(&self.tiled_windows(), focused, offset) {
+            self.windows.swap_windows(focused, target);
+            self.arrange()?;
+        }
+
+        Ok(())
+    }
+
+    /// swaps the focused window with the first tiled window (the main one),
+    /// or swaps the first window with the second when the first is focused, like dwm
+    pub fn zoom(&mut self) -> Result<(), XlibError> {
+        let Some(focused) = self.windows.focused().map(|w| w.window) else {
+            return Ok(());
+        };
+
+        let tiled: Vec<Window> = self.tiled_windows().iter().map(|w| w.window).collect();
+        let target = match tiled.iter().position(|w| *w == focused) {
+            Some(0) => tiled.get(1),
+            Some(_) => tiled.first(),
+            // floating windows aren't placed by the layout
+            None => None,
+        };
+
+        if let Some(&target) = target {
+            self.windows.swap_windows(focused, target);
+            self.arrange()?;
+        }
+
+        Ok(())
+    }
+
+    /// moves the window to the monitor it's mostly on, after it was moved by the user
+    fn update_window_monitor(&mut self, window: Window) -> Result<(), XlibError> {
+        let Some(win_state) = self.windows.get_window(window) else {
+            return Ok(());
+        };
+
+        let monitor =
+            self.monitor_for_geometry(win_state.x, win_state.y, win_state.width, win_state.height);
+        if monitor == win_state.monitor {
+            return Ok(());
+        }
+
+        let tags = self.monitors[monitor].tags.selected();
+        if let Some((_, win_state)) = self.windows.get_window_mut_by(|(_, w)| w.window == window) {
+            win_state.monitor = monitor;
+            win_state.tags = tags;
+        }
+        self.update_window_desktop(window)?;
+        self.focus_window(window)?;
+        self.arrange()?;
+
+        Ok(())
+    }
+
+    /// focuses the monitor `offset` monitors away from the selected one
+    pub fn focus_monitor(&mut self, offset: isize) -> Result<(), XlibError> {
+        let target = self.relative_monitor(offset);
+        if target == self.selected_monitor {
+            return Ok(());
+        }
+
+        self.selected_monitor = target;
+        self.focus_visible()?;
+        self.update_current_desktop()?;
+
+        Ok(())
+    }
+
+    /// moves the focused window to the monitor `offset` monitors away from the selected one
+    pub fn move_to_monitor(&mut self, offset: isize) -> Result<(), XlibError> {
+        let target = self.relative_monitor(offset);
+        if target == self.selected_monitor {
+            return Ok(());
+        }
+
+        let target_tags = self.monitors[target].tags.selected();
+        let from = self.current_monitor().bounding_box.clone();
+        let to = self.monitors[target].bounding_box.clone();
+
+        let Some(fw_state) = self.windows.focused_mut() else {
+            return Ok(());
+        };
+
+        fw_state.monitor = target;
+        fw_state.tags = target_tags;
+        if fw_state.is_floating {
+            // keep the same position relative to the monitor
+            fw_state.keep_relative_position(&from, &to);
+        }
+        let window = fw_state.window;
+
+        self.update_window_desktop(window)?;
+        self.focus_visible()?;
+        self.arrange()?;
+
+        Ok(())
+    }
+
+    /// sets `_NET_NUMBER_OF_DESKTOPS`, `_NET_DESKTOP_NAMES` and `_NET_CURRENT_DESKTOP`
+    fn update_desktop_properties(&self) -> Result<(), XlibError> {
+        let root = self.screen().root;
+
+        self.conn_wrapper.connection.change_property32(
+            PropMode::REPLACE,
+            root,
+            self.conn_wrapper.atoms._NET_NUMBER_OF_DESKTOPS,
+            AtomEnum::CARDINAL,
+            &[self.current_monitor().tags.len() as u32],
+        )?;
+
+        // names are null terminated and concatenated
+        let names = self
+            .current_monitor()
+            .tags
+            .names()
+            .iter()
+            .fold(Vec::new(), |mut acc, name| {
+                acc.extend_from_slice(name.as_bytes());
+                acc.push(0);
+                acc
+            });
+
+        self.conn_wrapper.connection.change_property8(
+            PropMode::REPLACE,
+            root,
+            self.conn_wrapper.atoms._NET_DESKTOP_NAMES,
+            self.conn_wrapper.atoms.UTF8_STRING,
+            &names,
+        )?;
+
+        self.update_current_desktop()
+    }
+
+    fn update_current_desktop(&self) -> Result<(), XlibError> {
+        self.conn_wrapper.connection.change_property32(
+            PropMode::REPLACE,
+            self.screen().root,
+            self.conn_wrapper.atoms._NET_CURRENT_DESKTOP,
+            AtomEnum::CARDINAL,
+            &[self.current_monitor().tags.current_desktop()],
         )?;
 
+        Ok(())
+    }
+
+    /// sets `_NET_WM_DESKTOP` of a managed window to its lowest tag
+    fn update_window_desktop(&self, window: Window) -> Result<(), XlibError> {
+        let Some(win_state) = self.windows.get_window(window) else {
+            return Ok(());
+        };
+
+        let tags = &self.monitors[win_state.monitor].tags;
+        let on_all_tags = tags.len() > 1 && win_state.tags == tags.all();
+        let desktop = if win_state.properties.is_sticky || on_all_tags {
+            ALL_DESKTOPS
+        } else {
+            first_tag(win_state.tags).unwrap_or_default()
+        };
+
+        self.conn_wrapper.connection.change_property32(
+            PropMode::REPLACE,
+            window,
+            self.conn_wrapper.atoms._NET_WM_DESKTOP,
+            AtomEnum::CARDINAL,
+            &[desktop],
+        )?;
+
+        Ok(())
+    }
+
+    /// sets `_NET_ACTIVE_WINDOW` to the focused window, `None` when nothing is focused
+    fn update_active_window(&self, window: Option<Window>) -> Result<(), XlibError> {
+        self.conn_wrapper.connection.change_property32(
+            PropMode::REPLACE,
+            self.screen().root,
+            self.conn_wrapper.atoms._NET_ACTIVE_WINDOW,
+            AtomEnum::WINDOW,
+            &[window.unwrap_or(NONE)],
+        )?;
+
+        Ok(())
+    }
+
+    /// shows and focuses a window, wherever it is, for `_NET_ACTIVE_WINDOW` requests
+    pub fn activate_window(&mut self, window: Window) -> Result<(), XlibError> {
+        let Some(win_state) = self.windows.get_window(window) else {
+            return Ok(());
+        };
+        let (monitor, tags, is_floating) =
+            (win_state.monitor, win_state.tags, win_state.is_floating);
+
+        // minimized windows are restored
+        if win_state.properties.is_hidden {
+            let hidden = self.conn_wrapper.atoms._NET_WM_STATE_HIDDEN;
+            self.change_net_wm_state(window, PropertyAction::Remove, &[hidden])?;
+        }
+        let Some(win_state) = self.windows.get_window(window) else {
+            return Ok(());
+        };
+
+        if monitor != self.selected_monitor {
+            self.selected_monitor = monitor;
+            self.update_current_desktop()?;
+        }
+
+        if !self.is_shown(win_state) && self.monitors[monitor].tags.view(tags) {
+            self.tags_changed()?;
+        }
+
+        self.focus_window(window)?;
+        if is_floating {
+            self.raise_window(window)?;
+            self.arrange()?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn focus_window(&mut self, window_handle: WindowHandle) -> Result<(), XlibError> {
+        if let Some(monitor) = self.windows.get_window(window_handle).map(|w| w.monitor) {
+            self.monitors[monitor].selected_window = Some(window_handle);
+            if monitor != self.selected_monitor {
+                self.selected_monitor = monitor;
+                self.update_current_desktop()?;
+            }
+        }
+
+        let previos_focus = self.windows.focus_window(window_handle)?.map(|w| w.window);
+        if let Some(previos_focus) = previos_focus {
+            if previos_focus != window_handle {
+                let change = ChangeWindowAttributesAux::new()
+                    .border_pixel(self.config.colors.normal_border.as_argb_u32());
+                self.conn_wrapper
+                    .connection
+                    .change_window_attributes(previos_focus, &change)?;
+            }
+        }
+        self.clear_urgency(window_handle)?;
+        if previos_focus != Some(window_handle) {
+            self.update_active_window(Some(window_handle))?;
+            self.emit(Event::Focus {
+                window: Some(window_handle),
+            });
+        }
+        let change = ChangeWindowAttributesAux::new()
+            .border_pixel(self.config.colors.focused_border.as_argb_u32());
+
+        self.conn_wrapper
+            .connection
+            .change_window_attributes(window_handle, &change)?;
+
+        if let Some(win_state) = self.windows.get_window(window_handle) {
+            self.set_input_focus(win_state)?;
+        }
+        self.raise_if_monocle(window_handle)?;
+
         let _ = self.draw_bar();
 
         self.conn_wrapper.connection.flush()?;
@@ -822,11 +1752,17 @@ impl<'a, C: Connection> WM<'a, C> {
 
     /// removes focus from currently focused window and sets input focus on root window
     fn unfocus(&mut self) -> Result<(), XlibError> {
-        if let Some(previos_focus) = self.windows.previos_focus() {
-            let change = ChangeWindowAttributesAux::new().border_pixel(RGBA::BLACK.as_argb_u32());
+        if self.windows.focused().is_some() {
+            self.update_active_window(None)?;
+            self.emit(Event::Focus { window: None });
+        }
+
+        if let Some(fw_state) = self.windows.focused() {
+            let change = ChangeWindowAttributesAux::new()
+                .border_pixel(self.config.colors.normal_border.as_argb_u32());
             self.conn_wrapper
                 .connection
-                .change_window_attributes(previos_focus.window, &change)?;
+                .change_window_attributes(fw_state.window, &change)?;
         }
 
         self.windows.unfocus();
@@ -898,32 +1834,23 @@ impl<'a, C: Connection> WM<'a, C> {
     }
 
     pub(crate) fn refresh(&mut self) {
-        while let Some(&win) = self.pending_expose.iter().next() {
-            self.pending_expose.remove(&win);
-            if let Some(win_state) = self.windows.get_window(win) {
-                if let Err(err) = self.draw_bar() {
-                    tracing::debug!(
-                        "Error while redrawing window {:x?}: {:?}",
-                        win_state.window,
-                        err
-                    );
-                }
+        if self.root_properties_dirty {
+            self.root_properties_dirty = false;
+            if let Err(err) = self.update_root_properties() {
+                tracing::warn!("failed to update the root window properties: {err}");
             }
         }
-    }
 
-    fn send_delete(&self, window: Window) -> Result<(), XlibError> {
-        let event = ClientMessageEvent::new(
-            32,
-            window,
-            self.conn_wrapper.atoms.WM_PROTOCOLS,
-            [self.conn_wrapper.atoms.WM_DELETE_WINDOW, 0, 0, 0, 0],
-        );
-        self.conn_wrapper
-            .connection
-            .send_event(false, window, EventMask::NO_EVENT, event)?;
-
-        Ok(())
+        // the bars are the only windows we draw on
+        let bar_exposed = self
+            .pending_expose
+            .drain()
+            .any(|win| self.monitors.iter().any(|m| m.bar.window == Some(win)));
+        if bar_exposed {
+            if let Err(err) = self.draw_bar() {
+                tracing::debug!("Error while redrawing the bar: {:?}", err);
+            }
+        }
     }
 
     fn conditionally_grab_pointer(&mut self, window: Window) -> Result<(), XlibError> {
@@ -947,6 +1874,58 @@ impl<'a, C: Connection> WM<'a, C> {
         Ok(())
     }
 
+    /// `_NET_WM_NAME`, falling back to `WM_NAME`
+    pub(crate) fn window_title(&self, window: Window) -> Result<String, XlibError> {
+        let reply = self
+            .conn_wrapper
+            .connection
+            .get_property(
+                false,
+                window,
+                self.conn_wrapper.atoms._NET_WM_NAME,
+                self.conn_wrapper.atoms.UTF8_STRING,
+                0,
+                u32::MAX,
+            )?
+            .reply()?;
+        if !reply.value.is_empty() {
+            return Ok(String::from_utf8_lossy(&reply.value).into_owned());
+        }
+
+        let reply = self
+            .conn_wrapper
+            .connection
+            .get_property(false, window, AtomEnum::WM_NAME, AtomEnum::ANY, 0, u32::MAX)?
+            .reply()?;
+
+        Ok(String::from_utf8_lossy(&reply.value).into_owned())
+    }
+
+    /// the instance and class names from `WM_CLASS`
+    pub(crate) fn window_class(&self, window: Window) -> Result<(String, String), XlibError> {
+        let reply = self
+            .conn_wrapper
+            .connection
+            .get_property(
+                false,
+                window,
+                AtomEnum::WM_CLASS,
+                AtomEnum::STRING,
+                0,
+                u32::MAX,
+            )?
+            .reply()?;
+
+        let mut names = reply
+            .value
+            .split(|&b| b == 0)
+            .map(|name| String::from_utf8_lossy(name).into_owned());
+        let instance = names.next().unwrap_or_default();
+        let class = names.next().unwrap_or_default();
+
+        Ok((instance, class))
+    }
+
     fn get_window_type(&self, window: Window) -> Result<Option<WindowType>, XlibError> {
         let window_types = self
             .conn_wrapper
@@ -969,76 +1948,8 @@ impl<'a, C: Connection> WM<'a, C> {
                     if v == self.conn_wrapper.atoms._NET_WM_WINDOW_TYPE_DESKTOP {
                         Some(WindowType::Desktop)
                     } else if v == self.conn_wrapper.atoms._NET_WM_WINDOW_TYPE_DOCK {
-                        if let Ok(reserved_space) = self
-                            .conn_wrapper
-                            .connection
-                            .get_property(
-                                false,
-                                window,
-                                self.conn_wrapper.atoms._NET_WM_STRUT_PARTIAL,
-                                AtomEnum::CARDINAL,
-                                0,
-                                12,
-                            )
-                            .map(|reserved_space| {
-                                // should not panic unless something is wrong with x server
-                                let reply = reserved_space.reply().unwrap();
-
-                                let reserved_space = reply.value32();
-
-                                if let Some(reserved_space) = reserved_space {
-                                    let reserved_space: Vec<u32> = reserved_space.collect();
-                                    let left_width = reserved_space[0];
-                                    let left_start_y = reserved_space[4];
-                                    let left_end_y = reserved_space[5];
-                                    let left = EdgeDimensions {
-                                        width: left_width,
-                                        start: left_start_y,
-                                        end: left_end_y,
-                                    };
-
-                                    let right_width = reserved_space[1];
-                                    let right_start_y = reserved_space[6];
-                                    let right_end_y = reserved_space[7];
-                                    let right = EdgeDimensions {
-                                        width: right_width,
-                                        start: right_start_y,
-                                        end: right_end_y,
-                                    };
-
-                                    let top_width = reserved_space[2];
-                                    let top_start_x = reserved_space[8];
-                                    let top_end_x = reserved_space[9];
-                                    let top = EdgeDimensions {
-                                        width: top_width,
-                                        start: top_start_x,
-                                        end: top_end_x,
-                                    };
-
-                                    let bottom_width = reserved_space[3];
-                                    let bottom_start_x = reserved_space[10];
-                                    let bottom_end_x = reserved_space[11];
-                                    let bottom = EdgeDimensions {
-                                        width: bottom_width,
-                                        start: bottom_start_x,
-                                        end: bottom_end_x,
-                                    };
-
-                                    ReservedEdges {
-                                        top,
-                                        right,
-                                        left,
-                                        bottom,
-                                    }
-                                } else {
-                                    ReservedEdges::default()
-                                }
-                            })
-                        {
-                            Some(WindowType::Dock(reserved_space))
-                        } else {
-                            Some(WindowType::Dock(ReservedEdges::default()))
-                        }
+                        // docks without struts don't reserve anything
+                        Some(WindowType::Dock(self.struts(window).unwrap_or_default()))
                     } else if v == self.conn_wrapper.atoms._NET_WM_WINDOW_TYPE_TOOLBAR {
                         Some(WindowType::Toolbar)
                     } else if v == self.conn_wrapper.atoms._NET_WM_WINDOW_TYPE_MENU {
@@ -1078,28 +1989,34 @@ impl<'a, C: Connection> WM<'a, C> {
                 .height(fsw_state.height as u32)
                 .x(fsw_state.x as i32)
                 .y(fsw_state.y as i32)
-                .border_width(BORDER_WIDTH);
+                .border_width(fsw_state.border_width(self.config.border_width));
             self.conn_wrapper
                 .connection
                 .configure_window(fsw_state.window, &configure)?;
             self.conn_wrapper
                 .update_net_wm_state(&fsw_state.properties, fsw_state.window)?;
-            if fsw_state.window == window {
+            let fsw_window = fsw_state.window;
+            self.emit(Event::Fullscreen {
+                window: fsw_window,
+                fullscreen: false,
+            });
+            if fsw_window == window {
                 return Ok(());
             }
         }
 
-        let screen_width = self.screen().width_in_pixels as u32;
-        let screen_height = self.screen().height_in_pixels as u32;
+        let Some(monitor) = self.windows.get_window(window).map(|w| w.monitor) else {
+            return Ok(());
+        };
+        let monitor_bbox = self.monitors[monitor].bounding_box.clone();
 
         if let Some((_, win_state)) = self.windows.get_window_mut_by(|(_, w)| w.window == window) {
             win_state.properties.is_fullscreen = true;
-            win_state.properties.above = true;
             let configure = ConfigureWindowAux::new()
-                .width(screen_width)
-                .height(screen_height)
-                .x(0)
-                .y(0)
+                .width(monitor_bbox.width as u32)
+                .height(monitor_bbox.height as u32)
+                .x(monitor_bbox.x as i32)
+                .y(monitor_bbox.y as i32)
                 .stack_mode(StackMode::ABOVE)
                 .border_width(0);
             self.conn_wrapper
@@ -1107,6 +2024,10 @@ impl<'a, C: Connection> WM<'a, C> {
                 .configure_window(win_state.window, &configure)?;
             self.conn_wrapper
                 .update_net_wm_state(&win_state.properties, win_state.window)?;
+            self.emit(Event::Fullscreen {
+                window,
+                fullscreen: true,
+            });
         }
 
         Ok(())
@@ -1121,13 +2042,17 @@ impl<'a, C: Connection> WM<'a, C> {
                 .height(win_state.height as u32)
                 .x(win_state.x as i32)
                 .y(win_state.y as i32)
-                .border_width(BORDER_WIDTH);
+                .border_width(win_state.border_width(self.config.border_width));
             self.conn_wrapper
                 .connection
                 .configure_window(win_state.window, &configure)?;
 
             self.conn_wrapper
                 .update_net_wm_state(&win_state.properties, win_state.window)?;
+            self.emit(Event::Fullscreen {
+                window,
+                fullscreen: false,
+            });
         }
         Ok(())
     }
@@ -1171,15 +2096,29 @@ impl<'a, C: Connection> WM<'a, C> {
             }
         }
 
-        let floating_windows: Vec<u32> = self
+        // the first window in the stacking order is the topmost one, so they are raised from the
+        // last one, and the higher layers are raised after the lower ones to end up above them.
+        // only the X stacking order changes, the window list keeps its order
+        let mut restacked_windows: Vec<(u8, u32)> = self
             .windows
-            .floating_windows_handles()
+            .stacked_windows()
             .into_iter()
-            .cloned()
+            .filter(|w| w.is_restacked())
+            .rev()
+            .map(|w| (w.stack_layer(), w.window))
             .collect();
+        restacked_windows.sort_by_key(|(layer, _)| *layer);
 
-        for window_handle in floating_windows {
-            self.raise_window(window_handle)?;
+        for (layer, window_handle) in restacked_windows {
+            let stack_mode = if layer == 0 {
+                StackMode::BELOW
+            } else {
+                StackMode::ABOVE
+            };
+            let configure = ConfigureWindowAux::new().stack_mode(stack_mode);
+            self.conn_wrapper
+                .connection
+                .configure_window(window_handle, &configure)?;
         }
 
         Ok(())
@@ -1191,32 +2130,21 @@ impl<'a, C: Connection> WM<'a, C> {
             .connection
             .configure_window(window, &configure)?;
 
-        self.windows.move_to_top(window);
+        self.windows.raise(window);
+        self.root_properties_dirty = true;
 
         Ok(())
     }
 
     fn handle_map_notify(&mut self, event: MapNotifyEvent) -> Result<(), XlibError> {
-        if let Some(window_type) = self.get_window_type(event.window)? {
-            match window_type {
-                WindowType::Dock(ReservedEdges {
-                    top,
-                    right,
-                    left,
-                    bottom,
-                }) => {
-                    self.layout_manager.reserved.top.width =
-                        self.layout_manager.reserved.top.width.max(top.width);
-                    self.layout_manager.reserved.bottom.width =
-                        self.layout_manager.reserved.bottom.width.max(bottom.width);
-                    self.layout_manager.reserved.left.width =
-                        self.layout_manager.reserved.left.width.max(left.width);
-                    self.layout_manager.reserved.right.width =
-                        self.layout_manager.reserved.right.width.max(right.width);
-                }
-                _ => {}
-            }
+        if self.windows.get_window(event.window).is_some() {
+            return Ok(());
+        }
+
+        if let Some(WindowType::Dock(_)) = self.get_window_type(event.window)? {
+            self.track_dock(event.window)?;
         }
+
         Ok(())
     }
 }
@@ -0,0 +1,244 @@
+use x11rb::{
+    connection::Connection,
+    properties::WmSizeHints,
+    protocol::xproto::{
+        ConfigureNotifyEvent, ConfigureWindowAux, ConnectionExt, EventMask, GrabMode, GrabStatus,
+        Keycode, Window, CONFIGURE_NOTIFY_EVENT,
+    },
+    CURRENT_TIME, NONE,
+};
+
+use crate::{
+    layout::{Layout, SIZE_WEIGHT_RANGE},
+    size_hints::SizeHints,
+    Direction, XlibError,
+};
+
+use super::WM;
+
+/// pixels a floating window is resized by for every key press in resize mode
+const RESIZE_MODE_STEP: i32 = 20;
+/// how much the main factor changes for every key press in resize mode
+const MAIN_FACTOR_STEP: f32 = 0.05;
+/// how much the size weight changes for every key press in resize mode
+const SIZE_WEIGHT_STEP: f32 = 0.25;
+
+impl<'a, C: Connection> WM<'a, C> {
+    /// the `WM_NORMAL_HINTS` of the window, empty if it doesn't have any
+    pub(crate) fn size_hints(&self, window: Window) -> Result<SizeHints, XlibError> {
+        let hints = WmSizeHints::get_normal_hints(self.conn_wrapper.connection, window)?.reply();
+
+        // a missing or malformed property is the same as no hints
+        Ok(hints.map(SizeHints::from).unwrap_or_default())
+    }
+
+    /// the focused window, if it's floating or its monitor uses the floating layout
+    fn focused_floating(&self) -> Option<u32> {
+        self.windows
+            .focused()
+            .filter(|w| {
+                w.is_floating || self.monitors[w.monitor].layout_manager.layout == Layout::Floating
+            })
+            .map(|w| w.window)
+    }
+
+    /// moves the focused floating window by the provided offset
+    pub fn move_floating(&mut self, dx: i32, dy: i32) -> Result<(), XlibError> {
+        let Some(win_state) = self
+            .focused_floating()
+            .and_then(|window| self.windows.get_window(window))
+            .filter(|w| w.can_move())
+        else {
+            return Ok(());
+        };
+
+        let window = win_state.window;
+        let x = (win_state.x as i32 + dx).clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+        let y = (win_state.y as i32 + dy).clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+
+        self.conn_wrapper
+            .connection
+            .configure_window(window, &ConfigureWindowAux::new().x(x as i32).y(y as i32))?;
+        if let Some((_, win_state)) = self.windows.get_window_mut_by(|(_, w)| w.window == window) {
+            win_state.x = x;
+            win_state.y = y;
+        }
+
+        // the window might have been moved to another monitor
+        self.update_window_monitor(window)
+    }
+
+    /// resizes the focused floating window by the provided amount, keeping its top left corner
+    pub fn resize_floating(&mut self, dw: i32, dh: i32) -> Result<(), XlibError> {
+        let Some(win_state) = self
+            .focused_floating()
+            .and_then(|window| self.windows.get_window(window))
+            .filter(|w| w.can_resize())
+        else {
+            return Ok(());
+        };
+
+        let window = win_state.window;
+        let (width, height) = win_state.size_hints.apply(
+            (win_state.width as i32 + dw).clamp(1, u16::MAX as i32) as u16,
+            (win_state.height as i32 + dh).clamp(1, u16::MAX as i32) as u16,
+        );
+
+        self.conn_wrapper.connection.configure_window(
+            window,
+            &ConfigureWindowAux::new()
+                .width(width as u32)
+                .height(height as u32),
+        )?;
+        if let Some((_, win_state)) = self.windows.get_window_mut_by(|(_, w)| w.window == window) {
+            win_state.width = width;
+            win_state.height = height;
+        }
+
+        Ok(())
+    }
+
+    /// tells a client the geometry it actually has, after ignoring its configure request
+    /// (ICCCM 4.1.5)
+    pub(crate) fn send_configure_notify(&self, window: Window) -> Result<(), XlibError> {
+        let Some(win_state) = self.windows.get_window(window) else {
+            return Ok(());
+        };
+
+        let (x, y, width, height, border_width) = if win_state.properties.is_fullscreen {
+            let bbox = &self.monitors[win_state.monitor].bounding_box;
+            (bbox.x, bbox.y, bbox.width, bbox.height, 0)
+        } else {
+            (
+                win_state.x,
+                win_state.y,
+                win_state.width,
+                win_state.height,
+                win_state.border_width(self.config.border_width),
+            )
+        };
+
+        let event = ConfigureNotifyEvent {
+            response_type: CONFIGURE_NOTIFY_EVENT,
+            sequence: 0,
+            event: window,
+            window,
+            above_sibling: NONE,
+            x,
+            y,
+            width,
+            height,
+            border_width: border_width as u16,
+            override_redirect: false,
+        };
+        self.conn_wrapper.connection.send_event(
+            false,
+            window,
+            EventMask::STRUCTURE_NOTIFY,
+            event,
+        )?;
+
+        Ok(())
+    }
+
+    /// grows (or shrinks with a negative `delta`) the share of its column
+    /// the focused tiled window gets
+    pub fn change_size_weight(&mut self, delta: f32) -> Result<(), XlibError> {
+        let Some(fw_state) = self.windows.focused_mut().filter(|w| !w.is_floating) else {
+            return Ok(());
+        };
+
+        let weight = (fw_state.size_weight + delta)
+            .clamp(*SIZE_WEIGHT_RANGE.start(), *SIZE_WEIGHT_RANGE.end());
+        if weight != fw_state.size_weight {
+            fw_state.size_weight = weight;
+            self.arrange()?;
+        }
+
+        Ok(())
+    }
+
+    /// grabs the keyboard so the arrow keys (or hjkl) resize the focused window,
+    /// until any other key is pressed
+    pub fn enter_resize_mode(&mut self) -> Result<(), XlibError> {
+        if self.resize_mode || self.windows.focused().is_none() {
+            return Ok(());
+        }
+
+        let status = self
+            .conn_wrapper
+            .connection
+            .grab_keyboard(
+                false,
+                self.screen().root,
+                CURRENT_TIME,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+            )?
+            .reply()?
+            .status;
+        if status != GrabStatus::SUCCESS {
+            tracing::warn!("couldn't grab the keyboard for resize mode: {status:?}");
+            return Ok(());
+        }
+
+        self.resize_mode = true;
+        Ok(())
+    }
+
+    fn exit_resize_mode(&mut self) -> Result<(), XlibError> {
+        self.resize_mode = false;
+        self.conn_wrapper.connection.ungrab_keyboard(CURRENT_TIME)?;
+        self.conn_wrapper.connection.flush()?;
+
+        Ok(())
+    }
+
+    /// floating windows are resized by pixels, for tiled windows left and right change
+    /// the main factor and up and down change the size weight of the focused window
+    pub(crate) fn handle_resize_mode_key(&mut self, code: Keycode) -> Result<(), XlibError> {
+        let keysym = self.keysyms.get(&code).copied().unwrap_or_default();
+        // modifiers on their own don't end the mode
+        if (x11_keysyms::XK_Shift_L..=x11_keysyms::XK_Hyper_R).contains(&keysym) {
+            return Ok(());
+        }
+
+        let direction = match keysym {
+            x11_keysyms::XK_Left | x11_keysyms::XK_h => Direction::Left,
+            x11_keysyms::XK_Right | x11_keysyms::XK_l => Direction::Right,
+            x11_keysyms::XK_Up | x11_keysyms::XK_k => Direction::Up,
+            x11_keysyms::XK_Down | x11_keysyms::XK_j => Direction::Down,
+            _ => return self.exit_resize_mode(),
+        };
+
+        if self.windows.focused().is_none() {
+            return self.exit_resize_mode();
+        }
+
+        if self.focused_floating().is_some() {
+            let (dw, dh) = direction.offset(RESIZE_MODE_STEP);
+            return self.resize_floating(dw, dh);
+        }
+
+        match direction {
+            Direction::Left | Direction::Right => {
+                let delta = if direction == Direction::Left {
+                    -MAIN_FACTOR_STEP
+                } else {
+                    MAIN_FACTOR_STEP
+                };
+                if self
+                    .current_monitor_mut()
+                    .layout_manager
+                    .change_main_factor(delta)
+                {
+                    self.arrange()?;
+                }
+            }
+            Direction::Up => self.change_size_weight(-SIZE_WEIGHT_STEP)?,
+            Direction::Down => self.change_size_weight(SIZE_WEIGHT_STEP)?,
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+use x11rb::{
+    connection::Connection,
+    protocol::xproto::{AtomEnum, ConfigureWindowAux, ConnectionExt, Window},
+};
+
+use crate::{
+    rules::{apply_rules, RuleActions, RuleTarget, WindowKind},
+    WindowState, XlibError,
+};
+
+use super::WM;
+
+impl<'a, C: Connection> WM<'a, C> {
+    /// the number of monitors isn't known when the config is parsed, so the rule monitors are
+    /// checked when the config is applied and when the monitors change.
+    /// rules with a missing monitor still work, the monitor is ignored when they match
+    pub(crate) fn check_rule_monitors(&self) {
+        for monitor in self
+            .config
+            .rules
+            .iter()
+            .filter_map(|rule| rule.actions.monitor)
+        {
+            if monitor >= self.monitors.len() {
+                tracing::warn!(
+                    "rule monitor {monitor} doesn't exist, there are {} monitors",
+                    self.monitors.len()
+                );
+            }
+        }
+    }
+
+    /// `WM_WINDOW_ROLE`, empty if the window doesn't have one
+    pub(crate) fn window_role(&self, window: Window) -> Result<String, XlibError> {
+        let reply = self
+            .conn_wrapper
+            .connection
+            .get_property(
+                false,
+                window,
+                self.conn_wrapper.atoms.WM_WINDOW_ROLE,
+                AtomEnum::STRING,
+                0,
+                u32::MAX,
+            )?
+            .reply()?;
+
+        Ok(String::from_utf8_lossy(&reply.value).into_owned())
+    }
+
+    /// the combined actions of the configured rules that match the window
+    pub(crate) fn window_rules(&self, win_state: &WindowState) -> Result<RuleActions, XlibError> {
+        if self.config.rules.is_empty() {
+            return Ok(RuleActions::default());
+        }
+
+        let window = win_state.window;
+        let (instance, class) = self.window_class(window)?;
+        let target = RuleTarget {
+            class,
+            instance,
+            title: win_state.title.clone(),
+            role: self.window_role(window)?,
+            kind: WindowKind::from(&win_state.r#type),
+        };
+
+        let actions = apply_rules(&self.config.rules, &target);
+        if actions != RuleActions::default() {
+            tracing::debug!("rules for {target:?}: {actions:?}");
+        }
+
+        Ok(actions)
+    }
+
+    /// applies the rule actions that have to be set before the window is managed,
+    /// fullscreen is applied by the caller once the window is in the window list
+    pub(crate) fn apply_rule_actions(
+        &self,
+        win_state: &mut WindowState,
+        actions: &RuleActions,
+    ) -> Result<(), XlibError> {
+        if let Some(monitor) = actions.monitor {
+            if monitor < self.monitors.len() {
+                // keep floating windows at the same place relative to their new monitor
+                let old_bbox = &self.monitors[win_state.monitor].bounding_box;
+                let new_bbox = &self.monitors[monitor].bounding_box;
+                win_state.keep_relative_position(old_bbox, new_bbox);
+
+                win_state.monitor = monitor;
+                win_state.tags = self.monitors[monitor].tags.selected();
+            } else {
+                tracing::warn!("rule monitor {monitor} doesn't exist, ignoring it");
+            }
+        }
+
+        if let Some(mask) = actions
+            .tag
+            .and_then(|index| self.monitors[win_state.monitor].tags.mask(index))
+        {
+            win_state.tags = mask;
+        }
+
+        if let Some(floating) = actions.floating {
+            win_state.is_floating = floating;
+        }
+
+        if let Some(sticky) = actions.sticky {
+            win_state.properties.is_sticky = sticky;
+            self.conn_wrapper
+                .update_net_wm_state(&win_state.properties, win_state.window)?;
+        }
+
+        if let Some(geometry) = &actions.geometry {
+            let bbox = &self.monitors[win_state.monitor].bounding_box;
+            if let Some(x) = geometry.x {
+                let x = bbox.x as i32 + x as i32;
+                win_state.x = x.clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+            }
+            if let Some(y) = geometry.y {
+                let y = bbox.y as i32 + y as i32;
+                win_state.y = y.clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+            }
+            (win_state.width, win_state.height) = win_state.size_hints.apply(
+                geometry.width.unwrap_or(win_state.width),
+                geometry.height.unwrap_or(win_state.height),
+            );
+        }
+
+        // tiled windows are placed by the layout
+        if win_state.is_floating {
+            let configure = ConfigureWindowAux::new()
+                .x(win_state.x as i32)
+                .y(win_state.y as i32)
+                .width(win_state.width as u32)
+                .height(win_state.height as u32);
+            self.conn_wrapper
+                .connection
+                .configure_window(win_state.window, &configure)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,147 @@
+use x11rb::{
+    connection::Connection,
+    protocol::xproto::{Atom, ConfigureWindowAux, ConnectionExt, Window},
+    NONE,
+};
+
+use crate::{PropertyAction, SavedGeometry, XlibError};
+
+use super::WM;
+
+impl<'a, C: Connection> WM<'a, C> {
+    /// changes up to two `_NET_WM_STATE` states of the window, like a client message does,
+    /// and reflects the result back in the property
+    ///
+    /// <https://specifications.freedesktop.org/wm-spec/1.3/ar01s05.html#idm45798289450576>
+    pub(crate) fn change_net_wm_state(
+        &mut self,
+        window: Window,
+        action: PropertyAction,
+        states: &[Atom],
+    ) -> Result<(), XlibError> {
+        let atoms = self.conn_wrapper.atoms;
+
+        for &state in states.iter().filter(|&&state| state != NONE) {
+            let Some((_, win_state)) = self.windows.get_window_mut_by(|(_, w)| w.window == window)
+            else {
+                return Ok(());
+            };
+            let Some(flag) = win_state.properties.state_mut(&atoms, state) else {
+                tracing::debug!("unsupported _NET_WM_STATE {state} for window {window}");
+                continue;
+            };
+
+            let value = action.apply(*flag);
+            if value == *flag {
+                continue;
+            }
+
+            // urgency and fullscreen keep track of their flag themselves
+            if state == atoms._NET_WM_STATE_DEMANDS_ATTENTION {
+                self.set_urgent(window, value)?;
+                continue;
+            }
+            if state == atoms._NET_WM_STATE_FULLSCREEN {
+                if value {
+                    self.fullscreen_window(window)?;
+                } else {
+                    self.unfullscreen_window(window)?;
+                }
+                self.arrange()?;
+                continue;
+            }
+
+            *flag = value;
+            // a window is either above or below the others, not both
+            if value && state == atoms._NET_WM_STATE_ABOVE {
+                win_state.properties.below = false;
+            } else if value && state == atoms._NET_WM_STATE_BELOW {
+                win_state.properties.above = false;
+            }
+
+            if state == atoms._NET_WM_STATE_MAXIMIZED_VERT
+                || state == atoms._NET_WM_STATE_MAXIMIZED_HORZ
+            {
+                self.update_maximized(window)?;
+                self.arrange()?;
+            } else if state == atoms._NET_WM_STATE_STICKY || state == atoms._NET_WM_STATE_HIDDEN {
+                self.update_window_desktop(window)?;
+                self.focus_visible()?;
+                self.arrange()?;
+            } else if state == atoms._NET_WM_STATE_ABOVE || state == atoms._NET_WM_STATE_BELOW {
+                self.arrange()?;
+            }
+        }
+
+        if let Some(win_state) = self.windows.get_window(window) {
+            tracing::debug!("new window state: {:?}", win_state.properties);
+            self.conn_wrapper
+                .update_net_wm_state(&win_state.properties, window)?;
+        }
+
+        Ok(())
+    }
+
+    /// maximized windows float over the usable area of their monitor along the maximized axes,
+    /// and get their old geometry back once they are not maximized anymore
+    fn update_maximized(&mut self, window: Window) -> Result<(), XlibError> {
+        let Some(win_state) = self.windows.get_window(window) else {
+            return Ok(());
+        };
+        let monitor = &self.monitors[win_state.monitor];
+        let area = monitor.layout_manager.usable_area(&monitor.bounding_box);
+        let border = win_state.border_width(self.config.border_width) as u16 * 2;
+
+        let Some((_, win_state)) = self.windows.get_window_mut_by(|(_, w)| w.window == window)
+        else {
+            return Ok(());
+        };
+        let vert = win_state.properties.is_maximized_vert;
+        let horz = win_state.properties.is_maximized_horz;
+
+        if !vert && !horz {
+            if let Some(saved) = win_state.unmaximized.take() {
+                win_state.x = saved.x;
+                win_state.y = saved.y;
+                win_state.width = saved.width;
+                win_state.height = saved.height;
+                win_state.is_floating = saved.is_floating;
+            }
+        } else {
+            let current = SavedGeometry {
+                x: win_state.x,
+                y: win_state.y,
+                width: win_state.width,
+                height: win_state.height,
+                is_floating: win_state.is_floating,
+            };
+            let saved = win_state.unmaximized.get_or_insert(current).clone();
+
+            win_state.is_floating = true;
+            (win_state.x, win_state.width) = if horz {
+                (area.x, area.width.saturating_sub(border).max(1))
+            } else {
+                (saved.x, saved.width)
+            };
+            (win_state.y, win_state.height) = if vert {
+                (area.y, area.height.saturating_sub(border).max(1))
+            } else {
+                (saved.y, saved.height)
+            };
+        }
+
+        // tiled windows are placed by the layout
+        if win_state.is_floating {
+            let configure = ConfigureWindowAux::new()
+                .x(win_state.x as i32)
+                .y(win_state.y as i32)
+                .width(win_state.width as u32)
+                .height(win_state.height as u32);
+            self.conn_wrapper
+                .connection
+                .configure_window(window, &configure)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+use x11rb::{
+    connection::Connection,
+    protocol::xproto::{AtomEnum, ChangeWindowAttributesAux, ConnectionExt, EventMask, Window},
+};
+
+use crate::{bar::BarPosition, layout::ReservedEdges, WindowState, WindowType, XlibError};
+
+use super::WM;
+
+impl<'a, C: Connection> WM<'a, C> {
+    /// the space the window reserves at the edges of the screen, from `_NET_WM_STRUT_PARTIAL`
+    /// or the older `_NET_WM_STRUT` when it doesn't have the partial one
+    ///
+    /// <https://specifications.freedesktop.org/wm-spec/1.3/ar01s05.html#idm45381391305328>
+    pub(crate) fn struts(&self, window: Window) -> Result<ReservedEdges, XlibError> {
+        let atoms = self.conn_wrapper.atoms;
+
+        let partial: Vec<u32> = self
+            .conn_wrapper
+            .connection
+            .get_property(
+                false,
+                window,
+                atoms._NET_WM_STRUT_PARTIAL,
+                AtomEnum::CARDINAL,
+                0,
+                12,
+            )?
+            .reply()?
+            .value32()
+            .map(|values| values.collect())
+            .unwrap_or_default();
+        if let Some(reserved) = ReservedEdges::from_strut_partial(&partial) {
+            return Ok(reserved);
+        }
+
+        let strut: Vec<u32> = self
+            .conn_wrapper
+            .connection
+            .get_property(false, window, atoms._NET_WM_STRUT, AtomEnum::CARDINAL, 0, 4)?
+            .reply()?
+            .value32()
+            .map(|values| values.collect())
+            .unwrap_or_default();
+
+        Ok(ReservedEdges::from_strut(&strut).unwrap_or_default())
+    }
+
+    /// recalculates the reserved space of every monitor from the struts of all the docks,
+    /// a monitor gets the largest strut on each of its edges.
+    /// our own bars reserve their height on their monitor, like dwm does
+    pub(crate) fn reserve_docks_space(&mut self) {
+        let (screen_width, screen_height) = self.screen_size;
+        let bars: Vec<Window> = self.monitors.iter().filter_map(|m| m.bar.window).collect();
+
+        let docks: Vec<(Window, usize, ReservedEdges)> = self
+            .windows
+            .unmanaged_windows()
+            .into_iter()
+            .filter_map(|w| match &w.r#type {
+                WindowType::Dock(reserved) => Some((
+                    w.window,
+                    self.monitor_for_geometry(w.x, w.y, w.width, w.height),
+                    reserved.clone(),
+                )),
+                _ => None,
+            })
+            .collect();
+
+        for monitor in self.monitors.iter_mut() {
+            let mut reserved = docks
+                .iter()
+                .filter(|(window, _, _)| !bars.contains(window))
+                .fold(ReservedEdges::default(), |reserved, (_, _, struts)| {
+                    reserved.max(&struts.relative_to(
+                        &monitor.bounding_box,
+                        screen_width,
+                        screen_height,
+                    ))
+                });
+
+            // the struts of the bars are measured from the screen edges for other programs,
+            // with stacked monitors they would cover the monitor next to the bar too
+            if monitor.bar.show && monitor.bar.window.is_some() {
+                let edge = match monitor.bar.pos {
+                    BarPosition::Top => &mut reserved.top,
+                    BarPosition::Bottom => &mut reserved.bottom,
+                };
+                edge.width = edge.width.max(monitor.bar.height as u32);
+            }
+
+            monitor.layout_manager.reserved = reserved;
+        }
+
+        // docks belong to the monitor they are placed on
+        for (window, monitor, _) in docks {
+            if let Some(dock) = self
+                .windows
+                .umanaged_windows_mut()
+                .into_iter()
+                .find(|w| w.window == window)
+            {
+                dock.monitor = monitor;
+            }
+        }
+    }
+
+    /// reads the struts of a dock again after it changed them
+    pub(crate) fn update_struts(&mut self, window: Window) -> Result<(), XlibError> {
+        let reserved = self.struts(window)?;
+        let Some(dock) = self
+            .windows
+            .umanaged_windows_mut()
+            .into_iter()
+            .find(|w| w.window == window && matches!(w.r#type, WindowType::Dock(_)))
+        else {
+            return Ok(());
+        };
+        if dock.r#type == WindowType::Dock(reserved.clone()) {
+            return Ok(());
+        }
+
+        dock.r#type = WindowType::Dock(reserved);
+        self.reserve_docks_space();
+        self.arrange()
+    }
+
+    /// keeps track of a dock that was mapped without asking, like override-redirect panels,
+    /// so its struts are honoured until it goes away
+    pub(crate) fn track_dock(&mut self, window: Window) -> Result<(), XlibError> {
+        let is_known = self.windows.get_window(window).is_some()
+            || self
+                .windows
+                .unmanaged_windows()
+                .iter()
+                .any(|w| w.window == window);
+        if is_known {
+            return self.update_struts(window);
+        }
+
+        let geom = self.conn_wrapper.connection.get_geometry(window)?.reply()?;
+        let reserved = self.struts(window)?;
+
+        let change = ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE);
+        self.conn_wrapper
+            .connection
+            .change_window_attributes(window, &change)?;
+
+        let mut win_state = WindowState::new(window, &geom, WindowType::Dock(reserved), false);
+        win_state.monitor = self.monitor_for_geometry(geom.x, geom.y, geom.width, geom.height);
+        self.windows.add_unmanaged_window(win_state);
+
+        self.reserve_docks_space();
+        self.arrange()
+    }
+
+    /// forgets a dock that went away and gives its space back to the other windows
+    pub(crate) fn remove_dock(&mut self, window: Window) -> Result<(), XlibError> {
+        let Some(removed) = self.windows.remove_unmanaged_window(window) else {
+            return Ok(());
+        };
+        if !matches!(removed.r#type, WindowType::Dock(_)) {
+            return Ok(());
+        }
+
+        self.reserve_docks_space();
+        self.arrange()
+    }
+}